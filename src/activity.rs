@@ -0,0 +1,200 @@
+use crate::attacks::{side, AttackMap};
+use crate::chess_board::{Color, PieceType, Position};
+use crate::pawns::{adjacent_files, row_mask, rows_in_front, FILE_A};
+
+// Middlegame and endgame weights in centipawns, to be tuned
+#[derive(Clone,Debug)]
+pub struct ActivityWeights {
+    // Per safe square above the usual count of the piece
+    pub knight_mobility : (i32, i32),
+    pub bishop_mobility : (i32, i32),
+    pub rook_mobility : (i32, i32),
+    pub queen_mobility : (i32, i32),
+    pub bishop_pair : (i32, i32),
+    pub rook_open_file : (i32, i32),
+    pub rook_semi_open_file : (i32, i32),
+    pub rook_seventh_rank : (i32, i32),
+    pub knight_outpost : (i32, i32),
+    // Piece in the enemy half without a single safe square
+    pub trapped_piece : (i32, i32),
+}
+
+impl Default for ActivityWeights {
+    fn default() -> Self {
+        ActivityWeights {
+            knight_mobility : (4, 4),
+            bishop_mobility : (5, 5),
+            rook_mobility : (2, 4),
+            queen_mobility : (1, 2),
+            bishop_pair : (30, 50),
+            rook_open_file : (25, 10),
+            rook_semi_open_file : (12, 6),
+            rook_seventh_rank : (20, 30),
+            knight_outpost : (20, 10),
+            trapped_piece : (-50, -50),
+        }
+    }
+}
+
+// Safe squares of a piece of average activity: fewer count against it
+fn mobility_base(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Knight => 4,
+        PieceType::Bishop => 6,
+        PieceType::Rook => 7,
+        PieceType::Queen => 13,
+        _ => 0,
+    }
+}
+
+// Terms of one side, each as (middlegame, endgame)
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct Activity {
+    pub mobility : (i32, i32),
+    pub bishop_pair : (i32, i32),
+    pub rooks : (i32, i32),
+    pub outposts : (i32, i32),
+    pub trapped : (i32, i32),
+}
+
+impl Activity {
+    pub fn total(&self) -> (i32, i32) {
+        [self.mobility, self.bishop_pair, self.rooks, self.outposts, self.trapped].iter()
+            .fold((0, 0), |(mg, eg), term| (mg + term.0, eg + term.1))
+    }
+}
+
+// Middlegame and endgame activity, White minus Black
+pub fn activity_score(attack_map: &AttackMap, weights: &ActivityWeights) -> (i32, i32) {
+    let (white_mg, white_eg) = activity(attack_map, Color::White, weights).total();
+    let (black_mg, black_eg) = activity(attack_map, Color::Black, weights).total();
+    (white_mg - black_mg, white_eg - black_eg)
+}
+
+// Terms of the pieces of `color`
+pub fn activity(attack_map: &AttackMap, color: Color, weights: &ActivityWeights) -> Activity {
+    let mut activity = Activity::default();
+    let (own, enemy) = (side(color), 1 - side(color));
+    let (own_pawns, enemy_pawns) = (attack_map.pawns[own], attack_map.pawns[enemy]);
+    // On the seventh rank a rook only helps with pawns to take or a king to shut in
+    let seventh_row = if color == Color::White { 6 } else { 1 };
+    let eighth_row = if color == Color::White { 7 } else { 0 };
+    let seventh_rank_target = enemy_pawns & row_mask(seventh_row) != 0
+        || attack_map.kings[enemy].is_some_and(|king| king.row == eighth_row);
+    let mut bishops = 0;
+    for (piece, attacks) in attack_map.pieces_of(color) {
+        let weight = match piece.piece_type {
+            PieceType::Knight => weights.knight_mobility,
+            PieceType::Bishop => weights.bishop_mobility,
+            PieceType::Rook => weights.rook_mobility,
+            PieceType::Queen => weights.queen_mobility,
+            _ => continue,
+        };
+        let position = piece.get_position();
+        // Squares held by enemy pawns are not safe for a piece
+        let safe_squares = (attacks & !attack_map.occupied[own] & !attack_map.pawn_attacks[enemy]).count_ones() as i32;
+        let extra = safe_squares - mobility_base(piece.piece_type);
+        add(&mut activity.mobility, (weight.0 * extra, weight.1 * extra));
+        // Deep in its own camp, a piece without squares is merely undeveloped
+        let relative_rank = if color == Color::White { position.row } else { 7 - position.row };
+        if safe_squares == 0 && relative_rank >= 4 {
+            add(&mut activity.trapped, weights.trapped_piece);
+        }
+        match piece.piece_type {
+            PieceType::Bishop => bishops += 1,
+            PieceType::Rook => {
+                let file = FILE_A << position.col;
+                if own_pawns & file == 0 {
+                    add(&mut activity.rooks, if enemy_pawns & file == 0 { weights.rook_open_file } else { weights.rook_semi_open_file });
+                }
+                if position.row == seventh_row && seventh_rank_target {
+                    add(&mut activity.rooks, weights.rook_seventh_rank);
+                }
+            },
+            PieceType::Knight if is_outpost(position, color, own_pawns, enemy_pawns) => {
+                add(&mut activity.outposts, weights.knight_outpost);
+            },
+            _ => {},
+        }
+    }
+    if bishops >= 2 {
+        activity.bishop_pair = weights.bishop_pair;
+    }
+    activity
+}
+
+// Square of the fourth to sixth rank defended by an own pawn, that no enemy pawn can ever attack
+fn is_outpost(position: Position, color: Color, own_pawns: u64, enemy_pawns: u64) -> bool {
+    let relative_rank = if color == Color::White { position.row } else { 7 - position.row };
+    if !(3..=5).contains(&relative_rank) {
+        return false;
+    }
+    let behind_row = if color == Color::White { position.row - 1 } else { position.row + 1 };
+    let beside = adjacent_files(position.col);
+    let supported = own_pawns & beside & row_mask(behind_row) != 0;
+    let attackable = enemy_pawns & beside & rows_in_front(position.row, color) != 0;
+    supported && !attackable
+}
+
+fn add(term: &mut (i32, i32), value: (i32, i32)) {
+    term.0 += value.0;
+    term.1 += value.1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::ChessBoard;
+
+    fn white(fen: &str) -> Activity {
+        activity(&AttackMap::new(&ChessBoard::from_fen(fen).unwrap()), Color::White, &ActivityWeights::default())
+    }
+
+    #[test]
+    fn mobility_counts_safe_squares() {
+        let weights = ActivityWeights::default();
+        let (k, _) = weights.knight_mobility;
+        assert_eq!(white("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").mobility, (4 * k, 4 * k));
+        assert_eq!(white("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").mobility, (-2 * k, -2 * k));
+        // c6 and e6 are covered by the d7 pawn
+        assert_eq!(white("4k3/3p4/8/8/3N4/8/8/4K3 w - - 0 1").mobility, (2 * k, 2 * k));
+    }
+
+    #[test]
+    fn bishop_pair() {
+        assert_eq!(white("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").bishop_pair, ActivityWeights::default().bishop_pair);
+        assert_eq!(white("4k3/8/8/8/8/8/8/2N1KB2 w - - 0 1").bishop_pair, (0, 0));
+    }
+
+    #[test]
+    fn rook_files_and_seventh_rank() {
+        let weights = ActivityWeights::default();
+        assert_eq!(white("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").rooks, weights.rook_open_file);
+        assert_eq!(white("4k3/8/p7/8/8/8/8/R3K3 w - - 0 1").rooks, weights.rook_semi_open_file);
+        assert_eq!(white("4k3/8/8/8/8/8/P7/R3K3 w - - 0 1").rooks, (0, 0));
+        let (open, seventh) = (weights.rook_open_file, weights.rook_seventh_rank);
+        assert_eq!(white("4k3/R7/8/8/8/8/8/4K3 w - - 0 1").rooks, (open.0 + seventh.0, open.1 + seventh.1));
+        // Nothing to attack on the seventh rank
+        assert_eq!(white("8/R7/4k3/8/8/8/8/4K3 w - - 0 1").rooks, open);
+    }
+
+    #[test]
+    fn knight_outpost() {
+        assert_eq!(white("4k3/8/8/3N4/4P3/8/8/4K3 w - - 0 1").outposts, ActivityWeights::default().knight_outpost);
+        // The c7 pawn can still chase the knight away
+        assert_eq!(white("4k3/2p5/8/3N4/4P3/8/8/4K3 w - - 0 1").outposts, (0, 0));
+        // Not supported by a pawn
+        assert_eq!(white("4k3/8/8/3N4/8/8/4P3/4K3 w - - 0 1").outposts, (0, 0));
+    }
+
+    #[test]
+    fn trapped_pieces() {
+        let trapped = ActivityWeights::default().trapped_piece;
+        // Bishop a7 shut in by b6, defended by c7
+        assert_eq!(white("1R6/B1p5/1p6/7k/8/8/8/4K3 w - - 0 1").trapped, trapped);
+        // Rook a8 behind its own knight and pawn
+        assert_eq!(white("RN6/P7/8/7k/8/8/8/4K3 w - - 0 1").trapped, trapped);
+        // At home the same knight is only undeveloped
+        assert_eq!(white("7k/8/8/8/8/1P6/2P5/N3K3 w - - 0 1").trapped, (0, 0));
+    }
+}
@@ -0,0 +1,46 @@
+use crate::chess_board::{ChessBoard, Color, Piece, PieceType, Position};
+
+// Attacked squares (row * 8 + col, as in pawns.rs) of every piece on the board. They are
+// computed once per evaluation and shared by the king safety and activity terms.
+#[derive(Clone,Debug)]
+pub struct AttackMap {
+    pub pieces : Vec<(Piece, u64)>,
+    // Indexed by side, White first
+    pub occupied : [u64; 2],
+    pub pawns : [u64; 2],
+    pub pawn_attacks : [u64; 2],
+    pub kings : [Option<Position>; 2],
+}
+
+impl AttackMap {
+    pub fn new(chess_board: &ChessBoard) -> Self {
+        let mut attack_map = AttackMap { pieces: vec![], occupied: [0; 2], pawns: [0; 2], pawn_attacks: [0; 2], kings: [None; 2] };
+        for piece in chess_board.get_friendly_pieces().into_iter().chain(chess_board.get_ennemy_pieces()) {
+            let side = side(piece.color);
+            let attacks = chess_board.get_attacked_squares(&piece).into_iter().fold(0, |mask, square| mask | bit(square));
+            attack_map.occupied[side] |= bit(piece.get_position());
+            match piece.piece_type {
+                PieceType::Pawn => {
+                    attack_map.pawns[side] |= bit(piece.get_position());
+                    attack_map.pawn_attacks[side] |= attacks;
+                },
+                PieceType::King => attack_map.kings[side] = Some(piece.get_position()),
+                _ => {},
+            }
+            attack_map.pieces.push((piece, attacks));
+        }
+        attack_map
+    }
+
+    pub fn pieces_of(&self, color: Color) -> impl Iterator<Item = &(Piece, u64)> {
+        self.pieces.iter().filter(move |(piece, _)| piece.color == color)
+    }
+}
+
+pub fn side(color: Color) -> usize {
+    if color == Color::White { 0 } else { 1 }
+}
+
+pub fn bit(position: Position) -> u64 {
+    1 << (position.row * 8 + position.col)
+}
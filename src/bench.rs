@@ -0,0 +1,50 @@
+use crate::chess_board::ChessBoard;
+use crate::search_limits::SearchLimits;
+use crate::search_pool::{SearchConfig, SearchPool};
+use std::time::Duration;
+
+// Opening, middlegame and endgame positions used to compare node counts between versions
+pub const BENCH_POSITIONS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "r1bq1rk1/ppp2ppp/2np1n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 7",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w - - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+];
+
+#[derive(Clone,Debug)]
+pub struct BenchResult {
+    pub name : &'static str,
+    pub nodes : u64,
+    pub elapsed : Duration,
+}
+
+// Searches every bench position at a fixed depth, once with the given configuration and once
+// with each frontier pruning switched off, to measure what every technique saves. The table is
+// cleared before each position so that its count does not depend on the positions before it.
+pub fn run_bench(config: &SearchConfig, depth: usize) -> Vec<BenchResult> {
+    let base = SearchConfig { threads: 1, ..config.clone() };
+    let variants = [
+        ("all pruning", base.clone()),
+        ("no futility", SearchConfig { futility_pruning: false, ..base.clone() }),
+        ("no razoring", SearchConfig { razoring: false, ..base.clone() }),
+        ("no delta", SearchConfig { delta_pruning: false, ..base.clone() }),
+        ("no frontier pruning", SearchConfig { futility_pruning: false, razoring: false, delta_pruning: false, ..base.clone() }),
+    ];
+    variants.into_iter()
+        .map(|(name, config)| {
+            let mut search_pool = SearchPool::new(config);
+            let mut nodes = 0;
+            let mut elapsed = Duration::ZERO;
+            for fen in BENCH_POSITIONS.iter() {
+                let chess_board = ChessBoard::from_fen(fen).expect("bench positions are valid");
+                search_pool.clear_hash();
+                let search_result = search_pool.start_minimax(&chess_board, SearchLimits::depth(depth));
+                nodes += search_result.nodes;
+                elapsed += search_result.elapsed;
+            }
+            BenchResult { name, nodes, elapsed }
+        })
+        .collect()
+}
@@ -1,3 +1,5 @@
+use crate::zobrist;
+
 const INITIAL_BOARD: [[(PieceType, Color); 8]; 8] = [
     [(PieceType::Rook, Color::White), (PieceType::Knight, Color::White), (PieceType::Bishop, Color::White), (PieceType::Queen, Color::White), (PieceType::King, Color::White), (PieceType::Bishop, Color::White), (PieceType::Knight, Color::White), (PieceType::Rook, Color::White)],
     [(PieceType::Pawn, Color::White); 8],
@@ -38,6 +40,11 @@ pub struct Piece {
     pub color : Color,
     has_moved : bool,
 }
+impl Piece {
+    pub fn get_position(&self) -> Position {
+        self.position
+    }
+}
 #[derive(Clone,Debug,PartialEq)]
 pub enum ChessMove {
     OrdinaryMove { from: Position, to: Position },
@@ -57,6 +64,21 @@ pub struct ChessBoard {
     white_positions : Vec<Position>,
     party_state: PartyState,
     turn: Color,
+    // Demi-coups depuis la dernière prise ou le dernier coup de pion (règle des cinquante coups)
+    halfmove_clock : usize,
+    // Hash des positions depuis ce coup irréversible, pour détecter les répétitions
+    history : Vec<u64>,
+}
+
+fn piece_directions(piece_type: PieceType) -> &'static [(i32, i32)] {
+    match piece_type {
+        PieceType::Rook => &[(0, 1), (0, -1), (1, 0), (-1, 0)],
+        PieceType::Bishop => &[(1, 1), (1, -1), (-1, -1), (-1, 1)],
+        PieceType::Knight => &[(2, 1), (2, -1), (-2, 1), (-2, -1), (1, 2), (1, -2), (-1, 2), (-1, -2)],
+        PieceType::Queen => &[(0, 1), (0, -1), (1, 0), (-1, 0),(1, 1), (1, -1), (-1, -1), (-1, 1)],
+        PieceType::King => &[  (1, 0),(1, 1),(0, 1),(-1, 1),(-1, 0), (-1, -1),(0, -1),(1, -1)],
+        _ => &[],
+    }
 }
 
 macro_rules! is_move_possible {
@@ -122,6 +144,12 @@ macro_rules! is_king_in_check {
 
 
 
+impl Default for ChessBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ChessBoard {
     pub fn new() -> Self {
         let mut black_positions = vec![];
@@ -149,14 +177,125 @@ impl ChessBoard {
             }
         }
         let turn = Color::White;
-        ChessBoard {board,black_positions,white_positions,party_state: PartyState::None,turn}
+        ChessBoard {board,black_positions,white_positions,party_state: PartyState::None,turn,halfmove_clock: 0,history: vec![]}
+    }
+
+    // Lit une position FEN (placement, trait, roques et compteur de demi-coups ; la prise en
+    // passant est ignorée). La rangée 0 du plateau est la rangée 1 de l'échiquier.
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or("empty FEN")?;
+        let turn = match fields.next().unwrap_or("w") {
+            "w" => Color::White,
+            "b" => Color::Black,
+            other => return Err(format!("invalid side to move '{}'", other)),
+        };
+        let castling = fields.next().unwrap_or("-");
+        let _en_passant = fields.next();
+        let halfmove_clock = match fields.next() {
+            Some(clock) => clock.parse().map_err(|_| format!("invalid halfmove clock '{}'", clock))?,
+            None => 0,
+        };
+
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(format!("expected 8 ranks, found {}", ranks.len()));
+        }
+        let mut board = vec![vec![None; 8]; 8];
+        let mut black_positions = vec![];
+        let mut white_positions = vec![];
+        for (i, rank) in ranks.iter().enumerate() {
+            let row = 7 - i;
+            let mut col = 0;
+            for c in rank.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    col += empty as usize;
+                    continue;
+                }
+                if col >= 8 {
+                    return Err(format!("rank {} is too long", row + 1));
+                }
+                let piece_type = match c.to_ascii_lowercase() {
+                    'p' => PieceType::Pawn,
+                    'n' => PieceType::Knight,
+                    'b' => PieceType::Bishop,
+                    'r' => PieceType::Rook,
+                    'q' => PieceType::Queen,
+                    'k' => PieceType::King,
+                    _ => return Err(format!("invalid piece '{}'", c)),
+                };
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let position = Position { row, col };
+                // Sans droit de roque, le roi et les tours sont considérés comme ayant bougé
+                let has_moved = match (piece_type, color) {
+                    (PieceType::Pawn, Color::White) => row != 1,
+                    (PieceType::Pawn, _) => row != 6,
+                    (PieceType::King, Color::White) => !castling.contains(['K', 'Q']),
+                    (PieceType::King, _) => !castling.contains(['k', 'q']),
+                    (PieceType::Rook, Color::White) => !(row == 0 && ((col == 7 && castling.contains('K')) || (col == 0 && castling.contains('Q')))),
+                    (PieceType::Rook, _) => !(row == 7 && ((col == 7 && castling.contains('k')) || (col == 0 && castling.contains('q')))),
+                    _ => true,
+                };
+                board[row][col] = Some(Piece { piece_type, position, color, has_moved });
+                match color {
+                    Color::Black => black_positions.push(position),
+                    _ => white_positions.push(position),
+                }
+                col += 1;
+            }
+            if col != 8 {
+                return Err(format!("rank {} does not have 8 squares", row + 1));
+            }
+        }
+        for color in [Color::White, Color::Black] {
+            let positions = if color == Color::White { &white_positions } else { &black_positions };
+            let kings = positions.iter().filter(|p| board[p.row][p.col].is_some_and(|piece| piece.piece_type == PieceType::King)).count();
+            if kings != 1 {
+                return Err(format!("{:?} must have exactly one king", color));
+            }
+        }
+        let mut chess_board = ChessBoard {board,black_positions,white_positions,party_state: PartyState::None,turn,halfmove_clock,history: vec![]};
+        chess_board._update_party();
+        Ok(chess_board)
     }
 
     pub fn update_board(&mut self, chess_move : ChessMove) -> Vec<ChessMove> {
+        let is_irreversible = match &chess_move {
+            ChessMove::OrdinaryMove { from, to } => self.board[to.row][to.col].is_some()
+                || self.board[from.row][from.col].is_some_and(|piece| piece.piece_type == PieceType::Pawn),
+            ChessMove::Castle { .. } => false,
+        };
+        if is_irreversible {
+            self.halfmove_clock = 0;
+            self.history.clear();
+        }
+        else {
+            self.halfmove_clock += 1;
+            self.history.push(self.get_hash());
+        }
         self.make_a_move(&chess_move);
-        return self._update_party();
+        self._update_party()
+
+    }
+    // Passe le trait à l'adversaire sans jouer (utilisé par le null-move pruning)
+    pub fn make_null_move(&mut self) -> Vec<ChessMove> {
+        self.change_turn();
+        self._update_party()
+    }
+
+    // Vrai si le coup met le roi adverse en échec, sans calculer les coups de la position suivante
+    pub fn gives_check(&self, chess_move: &ChessMove) -> bool {
+        self.preview_move(chess_move).is_king_in_check()
+    }
 
+    // Position après le coup, pour l'évaluer : ni les coups suivants ni l'état de la partie
+    // ne sont calculés, ni l'historique des répétitions mis à jour
+    pub fn preview_move(&self, chess_move: &ChessMove) -> ChessBoard {
+        let mut chess_board_clone = self.clone();
+        chess_board_clone.make_a_move(chess_move);
+        chess_board_clone
     }
+
     fn make_a_move(&mut self,chess_move : &ChessMove) {
         match chess_move {
             ChessMove::Castle { king,rook } => {
@@ -166,14 +305,13 @@ impl ChessBoard {
                     if let Some(mut rook_piece) = self.board[rook.row][rook.col] {
                         king_piece.has_moved = true;
                         rook_piece.has_moved = true;
-                        if rook.col == 6 {
-                            new_rook_col = rook.col - 2;
-                            new_king_col =  king.col + 2;
+                        if rook.col == 7 {
+                            new_rook_col = 5;
+                            new_king_col = 6;
                         }
                         else {
-                            new_rook_col = rook.col + 3;
-                            new_king_col =  king.col - 2;
-                            
+                            new_rook_col = 3;
+                            new_king_col = 2;
                         }
                         self.remove_piece_position(&rook_piece);
                         rook_piece.position.col = new_rook_col;
@@ -223,7 +361,7 @@ impl ChessBoard {
         if self.turn == Color::White { self.turn = Color::Black;} else { self.turn = Color::White;}
     }
 
-    fn get_piece_from_pos(&self,p: &Position) -> Option<Piece> {
+    pub fn get_piece_from_pos(&self,p: &Position) -> Option<Piece> {
         self.board[p.row][p.col]
     }
     fn add_piece_position(&mut self, piece: &Piece) {
@@ -250,7 +388,7 @@ impl ChessBoard {
     }
 
     pub fn get_turn(&self) -> Color {
-        return self.turn
+        self.turn
     }
     
     pub fn get_friendly_pieces(&self) -> Vec<Piece> {
@@ -283,58 +421,34 @@ impl ChessBoard {
     }
 
     fn get_castle_moves(&self,king: Piece) -> Vec<ChessMove> {
-        let row :usize;
         let mut chess_moves: Vec<ChessMove> = vec![];
-        let mut is_col_empty : bool = true;
-        if king.color == Color::Black { row = 0; }
-        else{ row = 7; }
-
-        if let Some(piece) = self.board[row][0] {
-            let start = piece.position.col + 1;
-            let end = king.position.col;
-        
-            // Vérifiez chaque case entre le roi et la tour
-            for col in start..end {
-                if self.board[king.position.row][col].is_some() {
-                    is_col_empty = false; // Trouvé une pièce, donc les cases ne sont pas vides
-                    break;
-                }
-            }
-            if is_col_empty == true {
-                if piece.piece_type == PieceType::Rook && !piece.has_moved {
-                    chess_moves.push(ChessMove::Castle { king: king.position, rook: piece.position });
-
-                }
-            }
+        let row :usize = if king.color == Color::Black { 7 } else { 0 };
+        if king.has_moved || king.position != (Position { row, col: 4 }) {
+            return chess_moves;
         }
 
-        is_col_empty = true;
-
-
-        if let Some(piece) = self.board[row][6] {
-            let end = piece.position.col - 1;
-            let start = king.position.col;
-        
+        for rook_col in [0, 7] {
+            let Some(piece) = self.board[row][rook_col] else {
+                continue;
+            };
+            if piece.piece_type != PieceType::Rook || piece.color != king.color || piece.has_moved {
+                continue;
+            }
             // Vérifiez chaque case entre le roi et la tour
-            for col in start..end {
-                if self.board[king.position.row][col].is_some() {
-                    is_col_empty = false; // Trouvé une pièce, donc les cases ne sont pas vides
-                    break;
-                }
+            let (start, end) = if rook_col == 0 { (1, 4) } else { (5, 7) };
+            if (start..end).any(|col| self.board[row][col].is_some()) {
+                continue;
             }
-            if is_col_empty == true {
-                if piece.piece_type == PieceType::Rook && !piece.has_moved {
-                    chess_moves.push(ChessMove::Castle { king: king.position, rook: piece.position });
-
-                }
+            // Le roi ne peut ni roquer en échec ni traverser une case attaquée
+            let crossed = Position { row, col: if rook_col == 0 { 3 } else { 5 } };
+            let mut chess_board_clone = self.clone();
+            chess_board_clone.make_a_move(&ChessMove::OrdinaryMove { from: king.position, to: crossed });
+            chess_board_clone.change_turn();
+            if !self.is_king_in_check() && !chess_board_clone.is_king_in_check() {
+                chess_moves.push(ChessMove::Castle { king: king.position, rook: piece.position });
             }
         }
-
-    
-    
         chess_moves
-    
-    
     }
 
 
@@ -345,14 +459,14 @@ impl ChessBoard {
             PieceType::Pawn => {
                 let forward = if piece.color == Color::Black { -1 } else { 1 };
 
-                if is_checking == false {
+                if !is_checking {
         
                     let one_step = Position { row: (piece.position.row as i32 + forward) as usize, col: piece.position.col };
                     if is_move_possible!(one_step.row as i32,one_step.col as i32) &&  self.is_any_piece(&one_step).is_none() {
                         chess_moves.push(ChessMove::OrdinaryMove { from: piece.position, to: one_step });
         
                         // Mouvement initial de deux cases du pion
-                        if piece.has_moved == false {
+                        if !piece.has_moved {
                             let two_steps = Position { row: (piece.position.row as i32 + 2 * forward) as usize, col: piece.position.col };
                             if self.is_any_piece(&one_step).is_none() && self.is_any_piece(&two_steps).is_none() {
                                 chess_moves.push(ChessMove::OrdinaryMove { from: piece.position, to: two_steps });
@@ -364,7 +478,7 @@ impl ChessBoard {
                     let capture_pos = Position { row: (piece.position.row as i32 + forward) as usize, col: (piece.position.col as i32 + offset) as usize };
                     if is_move_possible!(capture_pos.row as i32 , capture_pos.col as i32) {
                         if let Some(target_piece) = self.board[capture_pos.row][capture_pos.col] {
-                            if is_checking == true {
+                            if is_checking {
                                 if target_piece.color != piece.color && target_piece.piece_type == PieceType::King {
                                     chess_moves.push(ChessMove::OrdinaryMove { from: piece.position, to: capture_pos });
                                 }
@@ -379,12 +493,7 @@ impl ChessBoard {
                 }
                 vec![]
             },
-            PieceType::Rook => vec![(0, 1), (0, -1), (1, 0), (-1, 0)],
-            PieceType::Bishop => vec![(1, 1), (1, -1), (-1, -1), (-1, 1)],
-            PieceType::Knight => vec![(2, 1), (2, -1), (-2, 1), (-2, -1), (1, 2), (1, -2), (-1, 2), (-1, -2)],
-            PieceType::Queen => vec![(0, 1), (0, -1), (1, 0), (-1, 0),(1, 1), (1, -1), (-1, -1), (-1, 1)],
-            PieceType::King => vec![  (1, 0),(0, 1),(-1, 1),(-1, 0), (-1, -1),(0, -1),(1, -1)],
-            _ => vec![],
+            piece_type => piece_directions(piece_type).to_vec(),
         };
         if piece.piece_type != PieceType::Pawn {
             let is_single_move_piece = piece.piece_type == PieceType::Knight || piece.piece_type == PieceType::King;
@@ -407,12 +516,12 @@ impl ChessBoard {
                             if result.color != piece.color { //I can eat a piece
                                     match result.piece_type {
                                         PieceType::King => { 
-                                            if is_checking == true { 
+                                            if is_checking { 
                                                 chess_moves.push(ChessMove::OrdinaryMove { from: piece.position , to: position })
                                             };
                                         },
                                         _ => {
-                                            if is_checking == false {
+                                            if !is_checking {
                                                 chess_moves.push(ChessMove::OrdinaryMove { from: piece.position , to: position })
                                             };
                                         },
@@ -421,7 +530,7 @@ impl ChessBoard {
                             break;
                         }
                         else {
-                            if is_checking == false {
+                            if !is_checking {
                                 chess_moves.push(ChessMove::OrdinaryMove { from: piece.position , to: position });
                             }
                         }
@@ -444,11 +553,38 @@ impl ChessBoard {
     
     }
     
-    fn is_any_piece(&self, position: &Position) -> Option<Piece> {
-        match self.board[position.row][position.col] {
-            Some(piece) => Some(piece),
-            None => None,
+    // Cases contrôlées par une pièce : toutes les cases atteintes, vides ou occupées par l'un ou
+    // l'autre camp (une pièce défendue compte), et les deux diagonales de prise pour un pion
+    pub fn get_attacked_squares(&self, piece: &Piece) -> Vec<Position> {
+        let mut squares = vec![];
+        if piece.piece_type == PieceType::Pawn {
+            let forward = if piece.color == Color::Black { -1 } else { 1 };
+            for offset in [-1, 1] {
+                let (row, col) = (piece.position.row as i32 + forward, piece.position.col as i32 + offset);
+                if (0..8).contains(&row) && (0..8).contains(&col) {
+                    squares.push(Position { row: row as usize, col: col as usize });
+                }
+            }
+            return squares;
         }
+        let is_single_move_piece = piece.piece_type == PieceType::Knight || piece.piece_type == PieceType::King;
+        for (dx, dy) in piece_directions(piece.piece_type).iter() {
+            let (mut row, mut col) = (piece.position.row as i32 + dx, piece.position.col as i32 + dy);
+            while (0..8).contains(&row) && (0..8).contains(&col) {
+                let position = Position { row: row as usize, col: col as usize };
+                squares.push(position);
+                if is_single_move_piece || self.is_any_piece(&position).is_some() {
+                    break;
+                }
+                row += dx;
+                col += dy;
+            }
+        }
+        squares
+    }
+
+    fn is_any_piece(&self, position: &Position) -> Option<Piece> {
+        self.board[position.row][position.col]
     
     }
 
@@ -460,23 +596,11 @@ impl ChessBoard {
 
 
         for piece in opposite_pieces.iter() {
+            // En mode échec, seule la prise du roi est générée. Le roi adverse compte aussi :
+            // deux rois ne peuvent pas être voisins
             let moves = self.get_ordinary_moves(*piece,true);
-            for m in moves.iter() {
-                match piece.piece_type {
-                    PieceType::King => (),
-                    _ => {
-                        match m {
-                            ChessMove::OrdinaryMove { from: _, to } => {
-                                if is_king_in_check!(king.position,to,piece.piece_type,piece.color) {
-                                    return true;   
-                                }
-                            },
-                            _ => (),
-                        }
-
-                    }
-                }
-
+            if !moves.is_empty() && is_king_in_check!(king.position,piece.position,piece.piece_type,piece.color) {
+                return true;
             }
 
         }
@@ -486,6 +610,7 @@ impl ChessBoard {
     fn get_all_moves(&self) -> Vec<ChessMove> {
         let mut chess_moves = vec![];
         let pieces = self.get_friendly_pieces();
+        let king = self.get_king();
         for piece in pieces {
             match piece.piece_type {
                 PieceType::King => {
@@ -494,7 +619,7 @@ impl ChessBoard {
                         let mut chess_board_clone = self.clone();
                         Self::make_a_move(&mut chess_board_clone, m);
                         Self::change_turn(&mut chess_board_clone);
-                        if Self::is_king_in_check(&chess_board_clone) == false {
+                        if !Self::is_king_in_check(&chess_board_clone) {
                             chess_moves.push(m.clone());
                         }
                     }
@@ -504,14 +629,27 @@ impl ChessBoard {
                         let mut chess_board_clone = self.clone();
                         Self::make_a_move(&mut chess_board_clone, castle_move);
                         Self::change_turn(&mut chess_board_clone);
-                        if Self::is_king_in_check(&chess_board_clone) == false {
+                        if !Self::is_king_in_check(&chess_board_clone) {
                             chess_moves.push(castle_move.clone());
                         }
                     }
                 }
                 _ => {
                     let moves = self.get_ordinary_moves(piece,false);
-                    chess_moves.extend(moves);
+                    // Only a piece on a line with its king can be pinned
+                    if Self::is_aligned(&king.position, &piece.position) {
+                        for m in moves {
+                            let mut chess_board_clone = self.clone();
+                            Self::make_a_move(&mut chess_board_clone, &m);
+                            Self::change_turn(&mut chess_board_clone);
+                            if !Self::is_king_in_check(&chess_board_clone) {
+                                chess_moves.push(m);
+                            }
+                        }
+                    }
+                    else {
+                        chess_moves.extend(moves);
+                    }
                 }
             }
         }
@@ -519,15 +657,17 @@ impl ChessBoard {
     
     }
 
+    fn is_aligned(a: &Position, b: &Position) -> bool {
+        a.row == b.row || a.col == b.col
+            || i32::abs(a.row as i32 - b.row as i32) == i32::abs(a.col as i32 - b.col as i32)
+    }
+
     pub fn get_party_moves(&self) -> Vec<ChessMove>  {
         if self.is_king_in_check() {
-            let safe_moves = self.find_safe_moves();
-            return safe_moves
+            self.find_safe_moves()
         }
         else {
-            let moves = self.get_all_moves();
-
-            return moves;
+            self.get_all_moves()
         }
     }
 
@@ -538,19 +678,23 @@ impl ChessBoard {
 
         if self.is_king_in_check() {
             let safe_moves = self.find_safe_moves();
-            if safe_moves.len() == 0 {
+            if safe_moves.is_empty() {
                 self.change_party_state(PartyState::CheckMate);
-
             }
-            self.change_party_state(PartyState::Check);
-            return safe_moves
+            else {
+                self.change_party_state(PartyState::Check);
+            }
+            safe_moves
         }
         else {
             let moves = self.get_all_moves();
-            if moves.len() == 0 {
+            if moves.is_empty() {
                 self.change_party_state(PartyState::Pat);
             }
-            return moves;
+            else {
+                self.change_party_state(PartyState::None);
+            }
+            moves
 
         }
     }
@@ -593,11 +737,64 @@ impl ChessBoard {
     }
 
     pub fn get_party_state(&self) -> PartyState {
-        return self.party_state;
+        self.party_state
     }
 
     pub fn get_board(&self) -> Vec<Vec<Option<Piece>>> {
-        return self.board.clone();
+        self.board.clone()
+    }
+
+    // La position est déjà apparue depuis le dernier coup irréversible
+    pub fn is_repetition(&self) -> bool {
+        self.history.contains(&self.get_hash())
+    }
+
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
+    }
+
+    // Ni pion, ni tour, ni dame, et au plus une pièce mineure sur l'échiquier
+    pub fn has_insufficient_material(&self) -> bool {
+        let mut minor_pieces = 0;
+        for pos in self.white_positions.iter().chain(self.black_positions.iter()) {
+            match self.board[pos.row][pos.col].map(|piece| piece.piece_type) {
+                Some(PieceType::King) => (),
+                Some(PieceType::Knight) | Some(PieceType::Bishop) => minor_pieces += 1,
+                _ => return false,
+            }
+        }
+        minor_pieces <= 1
+    }
+
+    // Nulle par pat, répétition, règle des cinquante coups ou matériel insuffisant
+    pub fn is_draw(&self) -> bool {
+        self.party_state == PartyState::Pat || self.is_repetition() || self.is_fifty_move_draw() || self.has_insufficient_material()
+    }
+
+    pub fn get_hash(&self) -> u64 {
+        let mut hash = zobrist::turn_key(self.turn);
+        for pos in self.white_positions.iter().chain(self.black_positions.iter()) {
+            let piece = self.board[pos.row][pos.col].unwrap();
+            hash ^= zobrist::piece_key(piece.piece_type, piece.color, *pos);
+        }
+        // Deux positions identiques sauf pour les droits de roque n'ont pas les mêmes coups
+        for color in [Color::White, Color::Black] {
+            for rook_col in [0, 7] {
+                if self.has_castling_right(color, rook_col) {
+                    hash ^= zobrist::castling_key(color, rook_col == 7);
+                }
+            }
+        }
+        hash
+    }
+
+    // Le roi et la tour de ce côté sont sur leurs cases de départ et n'ont pas encore bougé
+    fn has_castling_right(&self, color: Color, rook_col: usize) -> bool {
+        let row = if color == Color::White { 0 } else { 7 };
+        let unmoved = |col: usize, piece_type: PieceType| {
+            matches!(self.board[row][col], Some(piece) if piece.piece_type == piece_type && piece.color == color && !piece.has_moved)
+        };
+        unmoved(4, PieceType::King) && unmoved(rook_col, PieceType::Rook)
     }
 
 
@@ -643,4 +840,188 @@ impl ChessBoard {
 
 
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Même position, couleurs inversées et plateau retourné : le camp qui a le trait est échangé
+    pub(crate) fn mirror_fen(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let turn = if fields.get(1) == Some(&"b") { "w" } else { "b" };
+        let castling = swap_case(fields.get(2).unwrap_or(&"-"));
+        format!("{} {} {} - {}", placement.join("/"), turn, castling, fields.get(4).unwrap_or(&"0"))
+    }
+
+    fn swap_case(text: &str) -> String {
+        text.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect()
+    }
+
+    fn board(fen: &str) -> ChessBoard {
+        ChessBoard::from_fen(fen).unwrap()
+    }
+
+    fn ordinary(from: (usize, usize), to: (usize, usize)) -> ChessMove {
+        ChessMove::OrdinaryMove { from: Position { row: from.0, col: from.1 }, to: Position { row: to.0, col: to.1 } }
+    }
+
+    fn moves_from(chess_board: &ChessBoard, row: usize, col: usize) -> Vec<Position> {
+        ChessBoard::get_party_moves(chess_board).into_iter()
+            .filter_map(|chess_move| match chess_move {
+                ChessMove::OrdinaryMove { from, to } if from == (Position { row, col }) => Some(to),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn king_moves_in_every_direction() {
+        let chess_board = board("7k/8/8/8/8/8/8/K7 w - - 0 1");
+        let mut targets = moves_from(&chess_board, 0, 0);
+        targets.sort_by_key(|position| (position.row, position.col));
+        assert_eq!(targets, vec![Position { row: 0, col: 1 }, Position { row: 1, col: 0 }, Position { row: 1, col: 1 }]);
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_the_pin_line() {
+        // Le cavalier cloué en e2 ne peut pas bouger, la tour clouée reste sur la colonne e
+        let knight_pinned = board("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1");
+        assert!(moves_from(&knight_pinned, 1, 4).is_empty());
+        let rook_pinned = board("4k3/4r3/8/8/8/8/4R3/4K3 w - - 0 1");
+        let targets = moves_from(&rook_pinned, 1, 4);
+        assert!(targets.contains(&Position { row: 6, col: 4 }));
+        assert!(targets.iter().all(|position| position.col == 4));
+        let bishop_pinned = board("4k3/8/8/8/b7/8/2B5/3K4 w - - 0 1");
+        let targets = moves_from(&bishop_pinned, 1, 2);
+        assert_eq!(targets, vec![Position { row: 2, col: 1 }, Position { row: 3, col: 0 }]);
+    }
+
+    #[test]
+    fn kings_never_stand_side_by_side() {
+        let chess_board = board("8/8/8/3k4/8/3K4/8/8 w - - 0 1");
+        let targets = moves_from(&chess_board, 2, 3);
+        assert!(!targets.is_empty());
+        assert!(targets.iter().all(|position| position.row != 3));
+    }
+
+    #[test]
+    fn knight_and_pawn_checks_are_detected() {
+        let knight_check = board("4k3/8/3N4/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(ChessBoard::get_party_state(&knight_check), PartyState::Check);
+        let pawn_check = board("8/8/8/4k3/3P4/8/8/4K3 b - - 0 1");
+        assert_eq!(ChessBoard::get_party_state(&pawn_check), PartyState::Check);
+        // Le roi ne peut pas non plus aller sur une case attaquée par un cavalier ou un pion
+        let guarded = board("7k/8/8/8/8/4p3/8/4K1n1 w - - 0 1");
+        let targets = moves_from(&guarded, 0, 4);
+        for square in [Position { row: 1, col: 3 }, Position { row: 1, col: 5 }, Position { row: 1, col: 4 }] {
+            assert!(!targets.contains(&square), "{:?}", square);
+        }
+    }
+
+    fn castles(chess_board: &ChessBoard) -> Vec<usize> {
+        ChessBoard::get_party_moves(chess_board).into_iter()
+            .filter_map(|chess_move| match chess_move {
+                ChessMove::Castle { rook, .. } => Some(rook.col),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn castling_on_both_sides() {
+        let mut white = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(castles(&white), vec![0, 7]);
+        let black = board("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+        assert_eq!(castles(&black), vec![0, 7]);
+        white.update_board(ChessMove::Castle { king: Position { row: 0, col: 4 }, rook: Position { row: 0, col: 7 } });
+        assert_eq!(white.get_piece_from_pos(&Position { row: 0, col: 6 }).unwrap().piece_type, PieceType::King);
+        assert_eq!(white.get_piece_from_pos(&Position { row: 0, col: 5 }).unwrap().piece_type, PieceType::Rook);
+        assert!(white.get_piece_from_pos(&Position { row: 0, col: 7 }).is_none());
+    }
+
+    #[test]
+    fn no_castling_through_an_attacked_square_or_out_of_check() {
+        // Le fou noir en h3 attaque f1 mais pas g1 : le roi traverserait une case attaquée
+        let through_attack = board("4k3/8/8/8/8/7b/8/R3K2R w KQ - 0 1");
+        assert_eq!(castles(&through_attack), vec![0]);
+        let in_check = board("4r2k/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert!(castles(&in_check).is_empty());
+    }
+
+    #[test]
+    fn no_castling_with_a_moved_rook_or_king() {
+        let mut chess_board = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        chess_board.update_board(ordinary((0, 7), (1, 7)));
+        chess_board.update_board(ordinary((7, 0), (6, 0)));
+        chess_board.update_board(ordinary((1, 7), (0, 7)));
+        chess_board.update_board(ordinary((6, 0), (7, 0)));
+        assert_eq!(castles(&chess_board), vec![0]);
+        chess_board.update_board(ordinary((0, 4), (0, 3)));
+        chess_board.update_board(ordinary((7, 7), (6, 7)));
+        chess_board.update_board(ordinary((0, 3), (0, 4)));
+        assert!(castles(&chess_board).is_empty());
+        // Sans droit de roque dans la FEN, la tour est considérée comme ayant bougé
+        assert_eq!(castles(&board("r3k2r/8/8/8/8/8/8/R3K2R w Q - 0 1")), vec![0]);
+    }
+
+    #[test]
+    fn knight_shuffles_repeat_the_position() {
+        let mut chess_board = ChessBoard::new();
+        let shuffle = [ordinary((0, 6), (2, 5)), ordinary((7, 6), (5, 5)), ordinary((2, 5), (0, 6)), ordinary((5, 5), (7, 6))];
+        for (index, chess_move) in shuffle.iter().enumerate() {
+            assert!(!chess_board.is_repetition(), "after {} moves", index);
+            chess_board.update_board(chess_move.clone());
+        }
+        // Retour à la position de départ, puis une troisième fois après un second cycle
+        assert!(chess_board.is_repetition() && chess_board.is_draw());
+        for chess_move in shuffle.iter() {
+            chess_board.update_board(chess_move.clone());
+        }
+        assert!(chess_board.is_repetition() && chess_board.is_draw());
+        // Un coup de pion est irréversible : plus rien ne se répète
+        chess_board.update_board(ordinary((1, 4), (3, 4)));
+        assert!(!chess_board.is_repetition() && !chess_board.is_draw());
+    }
+
+    #[test]
+    fn fifty_moves_without_capture_or_pawn_move() {
+        let mut chess_board = board("4k3/8/8/8/8/8/8/R3K3 w - - 99 80");
+        assert!(!chess_board.is_fifty_move_draw() && !chess_board.is_draw());
+        chess_board.update_board(ordinary((0, 0), (1, 0)));
+        assert!(chess_board.is_fifty_move_draw() && chess_board.is_draw());
+        assert!(board("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").is_fifty_move_draw());
+    }
+
+    #[test]
+    fn insufficient_material() {
+        for fen in ["8/8/8/4k3/8/8/8/4K3 w - - 0 1", "8/8/8/4k3/8/8/8/2B1K3 w - - 0 1", "8/8/8/4k3/8/8/8/1N2K3 b - - 0 1"] {
+            assert!(board(fen).has_insufficient_material() && board(fen).is_draw(), "{}", fen);
+        }
+        for fen in ["8/8/8/4k3/8/8/8/R3K3 w - - 0 1", "8/8/8/4k3/8/8/4P3/4K3 w - - 0 1", "8/8/8/4k3/8/8/8/1NB1K3 w - - 0 1"] {
+            assert!(!board(fen).has_insufficient_material() && !board(fen).is_draw(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn castling_rights_change_the_hash() {
+        let with_rights = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let without_rights = board("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1");
+        let white_king_side_only = board("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1");
+        assert_ne!(with_rights.get_hash(), without_rights.get_hash());
+        assert_ne!(with_rights.get_hash(), white_king_side_only.get_hash());
+        assert_ne!(without_rights.get_hash(), white_king_side_only.get_hash());
+    }
+
+    #[test]
+    fn moving_the_king_back_keeps_the_lost_rights_in_the_hash() {
+        let start = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let mut chess_board = start.clone();
+        chess_board.update_board(ordinary((0, 4), (0, 5)));
+        chess_board.update_board(ordinary((7, 0), (7, 1)));
+        chess_board.update_board(ordinary((0, 5), (0, 4)));
+        chess_board.update_board(ordinary((7, 1), (7, 0)));
+        assert_ne!(chess_board.get_hash(), start.get_hash());
+        assert_eq!(chess_board.get_hash(), board("r3k2r/8/8/8/8/8/8/R3K2R w k - 0 1").get_hash());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::activity::{self, ActivityWeights};
+use crate::attacks::AttackMap;
+use crate::chess_board::{ChessBoard, Color, Piece};
+use crate::king_safety;
+use crate::minimax::piece_value;
+use crate::pawns::{self, PawnTable};
+use crate::pst;
+use std::fmt;
+use std::sync::Arc;
+
+pub const EVALUATORS: [&str; 2] = ["positional", "material"];
+
+// Static evaluation of a position, in centipawns from the point of view of the side to move.
+// Mates and stalemates are scored by the search, an evaluator only sees the position. The
+// search threads share one evaluator, hence Send + Sync.
+pub trait Evaluator: Send + Sync + fmt::Debug {
+    fn evaluate(&self, chess_board: &ChessBoard) -> i32;
+
+    // Detail of the evaluation term by term. Evaluators without terms only give the score.
+    fn explain(&self, chess_board: &ChessBoard) -> EvalTrace {
+        EvalTrace { terms: vec![], phase: pst::game_phase(chess_board), score: self.evaluate(chess_board) }
+    }
+}
+
+// One evaluation term, for each side as (middlegame, endgame)
+#[derive(Clone,Debug,PartialEq)]
+pub struct EvalTerm {
+    pub name : &'static str,
+    pub white : (i32, i32),
+    pub black : (i32, i32),
+}
+
+impl EvalTerm {
+    fn new(name: &'static str, white: (i32, i32), black: (i32, i32)) -> Self {
+        EvalTerm { name, white, black }
+    }
+
+    // White minus Black, blended by the phase
+    pub fn tapered(&self, phase: i32) -> i32 {
+        pst::taper(self.white.0 - self.black.0, self.white.1 - self.black.1, phase)
+    }
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct EvalTrace {
+    pub terms : Vec<EvalTerm>,
+    pub phase : i32,
+    // The evaluation itself, from the point of view of the side to move. The tapered terms
+    // can be a few centipawns off their sum, each one is rounded on its own.
+    pub score : i32,
+}
+
+pub fn create_evaluator(name: &str) -> Result<Arc<dyn Evaluator>, String> {
+    match name {
+        "positional" => Ok(Arc::new(PositionalEvaluator::default())),
+        "material" => Ok(Arc::new(MaterialEvaluator)),
+        _ => Err(format!("unknown evaluator {} (expected one of {})", name, EVALUATORS.join(", "))),
+    }
+}
+
+// Piece values only, the original evaluation of the bot
+#[derive(Clone,Copy,Debug,Default)]
+pub struct MaterialEvaluator;
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&self, chess_board: &ChessBoard) -> i32 {
+        let friend_points : i32 = ChessBoard::get_friendly_pieces(chess_board).iter().map(|piece| piece_value(piece.piece_type)).sum();
+        let ennemy_points : i32 = ChessBoard::get_ennemy_pieces(chess_board).iter().map(|piece| piece_value(piece.piece_type)).sum();
+        friend_points - ennemy_points
+    }
+
+    fn explain(&self, chess_board: &ChessBoard) -> EvalTrace {
+        let pieces = all_pieces(chess_board);
+        EvalTrace {
+            terms : vec![material_term(&pieces)],
+            phase : pst::game_phase(chess_board),
+            score : self.evaluate(chess_board),
+        }
+    }
+}
+
+fn all_pieces(chess_board: &ChessBoard) -> Vec<Piece> {
+    chess_board.get_friendly_pieces().into_iter().chain(chess_board.get_ennemy_pieces()).collect()
+}
+
+fn side_pieces(pieces: &[Piece], color: Color) -> Vec<Piece> {
+    pieces.iter().filter(|piece| piece.color == color).copied().collect()
+}
+
+fn material_term(pieces: &[Piece]) -> EvalTerm {
+    let material = |color: Color| {
+        let points : i32 = pieces.iter().filter(|piece| piece.color == color).map(|piece| piece_value(piece.piece_type)).sum();
+        (points, points)
+    };
+    EvalTerm::new("material", material(Color::White), material(Color::Black))
+}
+
+// Material plus the tapered piece-square tables, pawn structure, king safety and piece activity
+#[derive(Debug,Default)]
+pub struct PositionalEvaluator {
+    pawn_table : PawnTable,
+    weights : ActivityWeights,
+}
+
+impl PositionalEvaluator {
+    pub fn with_weights(weights: ActivityWeights) -> Self {
+        PositionalEvaluator { weights, ..PositionalEvaluator::default() }
+    }
+
+    pub fn weights(&self) -> &ActivityWeights {
+        &self.weights
+    }
+}
+
+impl Evaluator for PositionalEvaluator {
+    fn evaluate(&self, chess_board: &ChessBoard) -> i32 {
+        let (mut mg, mut eg) = pst::pst_terms(chess_board);
+        // Les termes suivants sont comptés pour les Blancs
+        let sign = if chess_board.get_turn() == Color::White { 1 } else { -1 };
+        let (pawn_mg, pawn_eg) = pawns::pawn_score(chess_board, &self.pawn_table);
+        let attack_map = AttackMap::new(chess_board);
+        let (king_mg, king_eg) = king_safety::king_safety_score(&attack_map);
+        let (activity_mg, activity_eg) = activity::activity_score(&attack_map, &self.weights);
+        mg += sign * (pawn_mg + king_mg + activity_mg);
+        eg += sign * (pawn_eg + king_eg + activity_eg);
+        MaterialEvaluator.evaluate(chess_board) + pst::taper(mg, eg, pst::game_phase(chess_board))
+    }
+
+    fn explain(&self, chess_board: &ChessBoard) -> EvalTrace {
+        let pieces = all_pieces(chess_board);
+        let (white_pawns, white_passed) = pawns::side_pawn_terms(chess_board, Color::White);
+        let (black_pawns, black_passed) = pawns::side_pawn_terms(chess_board, Color::Black);
+        let attack_map = AttackMap::new(chess_board);
+        let white_king = king_safety::king_safety(&attack_map, Color::White);
+        let black_king = king_safety::king_safety(&attack_map, Color::Black);
+        let white_activity = activity::activity(&attack_map, Color::White, &self.weights);
+        let black_activity = activity::activity(&attack_map, Color::Black, &self.weights);
+        let middlegame = |value: i32| (value, 0);
+        EvalTrace {
+            terms : vec![
+                material_term(&pieces),
+                EvalTerm::new("piece-square", pst::pieces_square_sum(&side_pieces(&pieces, Color::White)),
+                    pst::pieces_square_sum(&side_pieces(&pieces, Color::Black))),
+                EvalTerm::new("pawn structure", white_pawns, black_pawns),
+                EvalTerm::new("passed pawns", white_passed, black_passed),
+                EvalTerm::new("king shield", middlegame(white_king.shield), middlegame(black_king.shield)),
+                EvalTerm::new("pawn storm", middlegame(white_king.storm), middlegame(black_king.storm)),
+                EvalTerm::new("king files", middlegame(white_king.open_files), middlegame(black_king.open_files)),
+                EvalTerm::new("king attack", middlegame(white_king.attack), middlegame(black_king.attack)),
+                EvalTerm::new("mobility", white_activity.mobility, black_activity.mobility),
+                EvalTerm::new("bishop pair", white_activity.bishop_pair, black_activity.bishop_pair),
+                EvalTerm::new("rooks", white_activity.rooks, black_activity.rooks),
+                EvalTerm::new("outposts", white_activity.outposts, black_activity.outposts),
+                EvalTerm::new("trapped pieces", white_activity.trapped, black_activity.trapped),
+            ],
+            phase : pst::game_phase(chess_board),
+            score : self.evaluate(chess_board),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::BENCH_POSITIONS;
+    use crate::chess_board::tests::mirror_fen;
+
+    // Bench positions with each side to move, plus king attack and activity positions
+    fn positions() -> Vec<String> {
+        let extra = [
+            "r1bq1rk1/pp3ppp/2n5/3Np1B1/2B5/5Q2/PPP2PPP/R3K2R w KQ - 0 1",
+            "6k1/1R3ppp/8/3N4/2P5/8/5PPP/6K1 w - - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        ];
+        BENCH_POSITIONS.iter().chain(extra.iter())
+            .flat_map(|fen| [fen.to_string(), fen.replacen(" w ", " b ", 1)])
+            .collect()
+    }
+
+    #[test]
+    fn explained_terms_add_up_to_the_evaluation() {
+        let evaluators: [Box<dyn Evaluator>; 2] = [Box::new(PositionalEvaluator::default()), Box::new(MaterialEvaluator)];
+        for evaluator in evaluators.iter() {
+            for fen in positions() {
+                let chess_board = ChessBoard::from_fen(&fen).unwrap();
+                let trace = evaluator.explain(&chess_board);
+                assert_eq!(trace.score, evaluator.evaluate(&chess_board), "{}", fen);
+                let sum: i32 = trace.terms.iter().map(|term| term.tapered(trace.phase)).sum();
+                let white_score = if chess_board.get_turn() == Color::White { trace.score } else { -trace.score };
+                // Chaque terme est arrondi séparément, au plus un centipion d'écart par terme
+                assert!((sum - white_score).abs() < trace.terms.len() as i32, "{}: {} against {}", fen, sum, white_score);
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored_positions_swap_the_terms() {
+        let evaluator = PositionalEvaluator::default();
+        for fen in positions() {
+            let chess_board = ChessBoard::from_fen(&fen).unwrap();
+            let mirrored = ChessBoard::from_fen(&mirror_fen(&fen)).unwrap();
+            assert_eq!(evaluator.evaluate(&chess_board), evaluator.evaluate(&mirrored), "{}", fen);
+            let (trace, mirrored_trace) = (evaluator.explain(&chess_board), evaluator.explain(&mirrored));
+            for (term, mirrored_term) in trace.terms.iter().zip(mirrored_trace.terms.iter()) {
+                assert_eq!((term.white, term.black), (mirrored_term.black, mirrored_term.white), "{} in {}", term.name, fen);
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::attacks::{bit, side, AttackMap};
+use crate::chess_board::{Color, PieceType, Position};
+use crate::pawns::{squares, FILE_A};
+
+// Middlegame weights in centipawns. In the endgame the king has to come out, the piece-square
+// table takes over and these terms count for nothing.
+// Own pawn one and two squares in front of the king, on its file or the next ones
+const SHIELD: [i32; 2] = [12, 6];
+// Enemy pawn on those files, by distance in rows to the king
+const STORM: [i32; 5] = [0, -8, -20, -12, -6];
+const OPEN_FILE: i32 = -25;
+// No own pawn but an enemy one
+const SEMI_OPEN_FILE: i32 = -12;
+// A lone attacker rarely mates, the penalty starts with the second one
+const MIN_ATTACKERS: usize = 2;
+// Indexed by the sum of the attack weights of the pieces aiming at the king zone
+const SAFETY_TABLE: [i32; 32] = [
+      0,   0,   1,   2,   4,   7,  10,  14,  19,  25,  31,  38,  46,  54,  63,  72,
+     82,  92, 103, 114, 126, 138, 150, 162, 175, 188, 200, 212, 224, 236, 248, 260,
+];
+
+fn attack_weight(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Knight | PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 5,
+        _ => 0,
+    }
+}
+
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct KingSafety {
+    pub shield : i32,
+    pub storm : i32,
+    pub open_files : i32,
+    pub attack : i32,
+}
+
+impl KingSafety {
+    pub fn total(&self) -> i32 {
+        self.shield + self.storm + self.open_files + self.attack
+    }
+}
+
+// Middlegame and endgame king safety, White minus Black
+pub fn king_safety_score(attack_map: &AttackMap) -> (i32, i32) {
+    let mg = king_safety(attack_map, Color::White).total() - king_safety(attack_map, Color::Black).total();
+    (mg, 0)
+}
+
+// Terms of the king of `color`
+pub fn king_safety(attack_map: &AttackMap, color: Color) -> KingSafety {
+    let Some(king_position) = attack_map.kings[side(color)] else {
+        return KingSafety::default();
+    };
+    let own_pawns = attack_map.pawns[side(color)];
+    let enemy_pawns = attack_map.pawns[1 - side(color)];
+    let forward: i32 = if color == Color::White { 1 } else { -1 };
+    let mut safety = KingSafety::default();
+    let first_col = king_position.col.saturating_sub(1);
+    let last_col = (king_position.col + 1).min(7);
+    for col in first_col..=last_col {
+        let file = FILE_A << col;
+        if own_pawns & file == 0 {
+            safety.open_files += if enemy_pawns & file == 0 { OPEN_FILE } else { SEMI_OPEN_FILE };
+        }
+        for square in squares((own_pawns | enemy_pawns) & file) {
+            // Rows from the king toward the enemy, only pawns in front of the king matter
+            let distance = (square as i32 / 8 - king_position.row as i32) * forward;
+            if distance <= 0 {
+                continue;
+            }
+            if own_pawns & 1 << square != 0 {
+                if let Some(bonus) = SHIELD.get(distance as usize - 1) {
+                    safety.shield += bonus;
+                }
+            } else if let Some(penalty) = STORM.get(distance as usize) {
+                safety.storm += penalty;
+            }
+        }
+    }
+    let zone = king_zone(king_position, forward);
+    let (mut attackers, mut weight) = (0, 0);
+    for (piece, attacks) in attack_map.pieces.iter() {
+        if piece.color != color && attack_weight(piece.piece_type) > 0 && attacks & zone != 0 {
+            attackers += 1;
+            weight += attack_weight(piece.piece_type);
+        }
+    }
+    if attackers >= MIN_ATTACKERS {
+        safety.attack = -SAFETY_TABLE[weight.min(SAFETY_TABLE.len() - 1)];
+    }
+    safety
+}
+
+// The king square and its neighbours, plus the three squares two rows in front of it
+fn king_zone(king: Position, forward: i32) -> u64 {
+    let mut zone = 0;
+    for row in -1..=2 {
+        for col in -1..=1 {
+            let (row, col) = (king.row as i32 + row * forward, king.col as i32 + col);
+            if (0..8).contains(&row) && (0..8).contains(&col) {
+                zone |= bit(Position { row: row as usize, col: col as usize });
+            }
+        }
+    }
+    zone
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::ChessBoard;
+
+    fn white_king(fen: &str) -> KingSafety {
+        king_safety(&AttackMap::new(&ChessBoard::from_fen(fen).unwrap()), Color::White)
+    }
+
+    #[test]
+    fn pawn_shield() {
+        let intact = white_king("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1");
+        assert_eq!(intact, KingSafety { shield: 3 * SHIELD[0], ..KingSafety::default() });
+        // g3 still covers the king from one row further, g4 no longer does
+        assert_eq!(white_king("6k1/8/8/8/8/6P1/5P1P/6K1 w - - 0 1").shield, 2 * SHIELD[0] + SHIELD[1]);
+        assert_eq!(white_king("6k1/8/8/8/6P1/8/5P1P/6K1 w - - 0 1").shield, 2 * SHIELD[0]);
+    }
+
+    #[test]
+    fn pawn_storm() {
+        assert_eq!(white_king("6k1/8/8/8/7p/8/5PPP/6K1 w - - 0 1").storm, STORM[3]);
+        assert_eq!(white_king("6k1/8/8/8/8/7p/5PPP/6K1 w - - 0 1").storm, STORM[2]);
+        // Too far away to count yet
+        assert_eq!(white_king("6k1/8/7p/8/8/8/5PPP/6K1 w - - 0 1").storm, 0);
+    }
+
+    #[test]
+    fn open_files_next_to_the_king() {
+        let open = white_king("6k1/8/8/8/8/8/5P1P/6K1 w - - 0 1");
+        assert_eq!(open.open_files, OPEN_FILE);
+        let semi_open = white_king("6k1/8/6p1/8/8/8/5P1P/6K1 w - - 0 1");
+        assert_eq!((semi_open.open_files, semi_open.storm), (SEMI_OPEN_FILE, 0));
+        // Files away from the king do not matter
+        assert_eq!(white_king("6k1/8/8/8/8/8/1PP2PPP/6K1 w - - 0 1").open_files, 0);
+    }
+
+    #[test]
+    fn attack_grows_with_the_attackers() {
+        // Knight g5, then queen c6 and rook a3 all aim at f3-h3
+        let knight = white_king("6k1/8/8/6n1/8/8/5PPP/6K1 w - - 0 1");
+        let queen = white_king("6k1/8/2q5/6n1/8/8/5PPP/6K1 w - - 0 1");
+        let rook = white_king("6k1/8/2q5/6n1/8/r7/5PPP/6K1 w - - 0 1");
+        assert_eq!(knight.attack, 0);
+        assert_eq!(queen.attack, -SAFETY_TABLE[2 + 5]);
+        assert_eq!(rook.attack, -SAFETY_TABLE[2 + 5 + 3]);
+        assert!(rook.total() < queen.total() && queen.total() < knight.total());
+    }
+}
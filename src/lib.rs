@@ -0,0 +1,20 @@
+pub mod activity;
+pub mod attacks;
+pub mod bench;
+pub mod chess_board;
+pub mod evaluator;
+pub mod king_safety;
+pub mod mate_finder;
+pub mod mcts;
+pub mod minimax;
+pub mod observer;
+pub mod pawns;
+pub mod proof_number;
+pub mod pst;
+pub mod rng;
+pub mod search_limits;
+pub mod search_pool;
+pub mod searcher;
+pub mod skill;
+pub mod transposition;
+mod zobrist;
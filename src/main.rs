@@ -1,40 +1,363 @@
 
 
-mod chess_board;
-mod minimax;
+use std::env;
+use std::time::{Duration, Instant};
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use minimax_chess::bench;
+use minimax_chess::chess_board::{self, Color, PieceType, Position};
+use minimax_chess::evaluator::{create_evaluator, Evaluator};
+use minimax_chess::mate_finder::{MateFinder, MateResult, MATE_PROBLEMS};
+use minimax_chess::minimax::{self, Score, SearchResult};
+use minimax_chess::observer::{SearchInfo, SearchObserver};
+use minimax_chess::pst::MAX_PHASE;
+use minimax_chess::proof_number::{ProofNumberSearch, ProofResult, DEFAULT_MAX_MOVES, DEFAULT_MAX_NODES, KRK_POSITIONS};
+use minimax_chess::search_limits::SearchLimits;
+use minimax_chess::rng::Rng;
+use minimax_chess::search_pool::{SearchConfig, SearchHandle};
+use minimax_chess::searcher::{create_searcher, Searcher};
+use minimax_chess::skill::SkilledSearcher;
 
+use minimax_chess::chess_board::{ChessBoard, ChessMove};
 
-use std::{thread,time};
-use std::io;
-use chess_board::{Color, PieceType,Position};
+// Drop in centipawns from the best move above which a move is flagged
+const MISTAKE_THRESHOLD: i32 = 100;
+const BLUNDER_THRESHOLD: i32 = 250;
 
-use crate::chess_board::{ChessBoard, ChessMove};
+struct CliArgs {
+    command : Option<String>,
+    fen : Option<String>,
+    engine : String,
+    seed : Option<u64>,
+    skill : Option<u8>,
+    ponder : bool,
+    config : SearchConfig,
+    limits : SearchLimits,
+}
 
 fn main () {
-    let five_sec = time::Duration::from_millis(2000);
+    let cli_args = parse_args();
+    match cli_args.command.as_deref() {
+        None | Some("play") => {
+            // A deterministic game replays the same random choices too
+            let rng = match cli_args.seed {
+                Some(seed) => Rng::new(seed),
+                None if cli_args.config.deterministic => Rng::new(0),
+                None => Rng::from_time(),
+            };
+            let mut searcher: Box<dyn Searcher> = match cli_args.skill {
+                Some(level) if cli_args.engine == "minimax" => {
+                    let skilled_searcher = SkilledSearcher::new(cli_args.config, level, rng);
+                    let skill = skilled_searcher.skill();
+                    println!("skill level {} (about {} Elo)", skill.level, skill.elo);
+                    Box::new(skilled_searcher)
+                },
+                Some(_) => panic!("--skill only applies to the minimax engine"),
+                None => create_searcher(&cli_args.engine, cli_args.config, rng).unwrap_or_else(|error| panic!("{}", error)),
+            };
+            let observer = Arc::new(ConsoleObserver::default());
+            searcher.set_observer(observer.clone());
+            play(searcher, observer, cli_args.limits, cli_args.ponder)
+        },
+        Some("bench") => run_bench(&cli_args.config, cli_args.limits.depth.unwrap_or(4)),
+        Some("eval") => explain_eval(&load_board(cli_args.fen.as_deref()), cli_args.config.evaluator.as_ref()),
+        Some("analyze") => analyze(&load_board(cli_args.fen.as_deref()), &cli_args.config, cli_args.limits),
+        Some("mate") => find_mate(&load_board(cli_args.fen.as_deref()), cli_args.limits.mate.unwrap_or(3)),
+        Some("problems") => solve_problems(),
+        Some("prove") => prove(cli_args.fen.as_deref(), &cli_args.limits),
+        Some(command) => panic!("unknown command {}", command),
+    }
+}
+
+fn play(mut searcher: Box<dyn Searcher>, observer: Arc<ConsoleObserver>, search_limits: SearchLimits, ponder: bool) {
     let mut chess_board = chess_board::ChessBoard::new();
+    // Reply expected from the human, and the search already running on the position after it
+    let mut pondering: Option<(ChessMove, SearchHandle)> = None;
     loop {
         print_board(&chess_board);
 
         println!("Au tour des Blancs. Entrez votre mouvement (format attendu : 'x y x_dest y_dest') : ");
         let (from_pos, to_pos) = read_user_input();
         let chess_move = ChessMove::OrdinaryMove  { from : from_pos, to : to_pos};
+        let ponder_search = match pondering.take() {
+            Some((expected_move, search_handle)) if expected_move == chess_move => {
+                println!("ponder hit");
+                observer.set_muted(false);
+                search_handle.ponderhit();
+                Some(search_handle)
+            },
+            Some((_, search_handle)) => {
+                println!("ponder miss");
+                search_handle.stop();
+                search_handle.wait();
+                observer.set_muted(false);
+                None
+            },
+            None => None,
+        };
         ChessBoard::update_board(&mut chess_board, chess_move);
 
         print_board(&chess_board); // Assurez-vous que cette fonction existe et est correctement importée
         println!("turn : {:?}",ChessBoard::get_turn(&chess_board));
-        thread::sleep(five_sec);
-        let computer_move =  minimax::start_minimax(&chess_board).expect("should be a move");
+        let search_result = match ponder_search {
+            Some(search_handle) => search_handle.wait(),
+            None => searcher.search(&chess_board, search_limits.clone()),
+        };
+        print_search_result(&search_result);
+        let computer_move = search_result.best_move.expect("should be a move");
         println!("the Black bot choose : {:?}",computer_move);
         ChessBoard::update_board(&mut chess_board, computer_move);
+
+        if let Some(expected_move) = search_result.principal_variation.get(1).filter(|_| ponder) {
+            let mut expected_board = chess_board.clone();
+            if !ChessBoard::update_board(&mut expected_board, expected_move.clone()).is_empty() {
+                // The human is typing: the ponder search stays quiet until a ponder hit
+                observer.set_muted(true);
+                pondering = searcher.start_ponder(&expected_board, search_limits.clone())
+                    .map(|search_handle| (expected_move.clone(), search_handle));
+                observer.set_muted(pondering.is_some());
+            }
+        }
+    }
+}
+
+// Prints the search progress as UCI-like `info` lines, except while muted for pondering
+#[derive(Default)]
+struct ConsoleObserver {
+    muted : AtomicBool,
+}
+
+impl ConsoleObserver {
+    fn set_muted(&self, muted: bool) {
+        self.muted.store(muted, Ordering::Relaxed);
+    }
+
+    fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+}
+
+impl SearchObserver for ConsoleObserver {
+    fn on_depth(&self, info: &SearchInfo) {
+        if self.is_muted() {
+            return;
+        }
+        println!("info depth {} seldepth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+            info.depth, info.seldepth, info.multi_pv, format_score(info.score), info.nodes, info.nps,
+            info.elapsed.as_millis(), format_line(&info.principal_variation));
+    }
+
+    fn on_progress(&self, nodes: u64, nps: u64, elapsed: Duration) {
+        if self.is_muted() {
+            return;
+        }
+        println!("info nodes {} nps {} time {}", nodes, nps, elapsed.as_millis());
+    }
+}
+
+fn load_board(fen: Option<&str>) -> ChessBoard {
+    match fen {
+        Some(fen) => ChessBoard::from_fen(fen).unwrap_or_else(|error| panic!("invalid FEN: {}", error)),
+        None => ChessBoard::new(),
+    }
+}
+
+fn analyze(chess_board: &ChessBoard, search_config: &SearchConfig, search_limits: SearchLimits) {
+    print_board(chess_board);
+    let analysis = minimax::analyze_all_moves_with_config(chess_board, search_config, search_limits);
+    let Some((_, best_score)) = analysis.first() else {
+        println!("no legal move");
+        return;
+    };
+    let best_value = best_score.to_value();
+    for (index, (chess_move, score)) in analysis.iter().enumerate() {
+        let loss = best_value - score.to_value();
+        let flag = if loss >= BLUNDER_THRESHOLD { "?? blunder" } else if loss >= MISTAKE_THRESHOLD { "?  mistake" } else { "" };
+        println!("{:>3}. {:<8}{:<14}{}", index + 1, format_move(chess_move), format_score(*score), flag);
+    }
+}
+
+// Evaluation term by term, per side and phase. The last column is White minus Black, tapered.
+fn explain_eval(chess_board: &ChessBoard, evaluator: &dyn Evaluator) {
+    print_board(chess_board);
+    let trace = evaluator.explain(chess_board);
+    println!("{:<16}{:>10}{:>10}{:>10}{:>10}{:>10}", "term", "white mg", "white eg", "black mg", "black eg", "total");
+    for term in trace.terms.iter() {
+        println!("{:<16}{:>10}{:>10}{:>10}{:>10}{:>10}", term.name, term.white.0, term.white.1,
+            term.black.0, term.black.1, term.tapered(trace.phase));
+    }
+    let white_score = if ChessBoard::get_turn(chess_board) == Color::White { trace.score } else { -trace.score };
+    println!("phase {}/{} (0 is a pure endgame)", trace.phase, MAX_PHASE);
+    println!("evaluation {} cp for White, {} cp for the side to move", white_score, trace.score);
+}
+
+fn run_bench(search_config: &SearchConfig, depth: usize) {
+    println!("bench depth {} on {} positions", depth, bench::BENCH_POSITIONS.len());
+    let results = bench::run_bench(search_config, depth);
+    let reference = results.first().map_or(1, |result| result.nodes.max(1));
+    println!("{:<22}{:>12}{:>10}{:>10}", "configuration", "nodes", "ratio", "ms");
+    for result in results.iter() {
+        println!("{:<22}{:>12}{:>10.2}{:>10}", result.name, result.nodes,
+            result.nodes as f64 / reference as f64, result.elapsed.as_millis());
+    }
+}
+
+fn find_mate(chess_board: &ChessBoard, max_moves: usize) {
+    print_board(chess_board);
+    let mut mate_finder = MateFinder::new();
+    let start = Instant::now();
+    match mate_finder.find_mate(chess_board, max_moves) {
+        MateResult::Mate { moves, line } => println!("mate in {}: {}", moves, format_line(&line)),
+        MateResult::NoMate => println!("no mate in {}", max_moves),
+    }
+    println!("nodes {} time {} ms", mate_finder.nodes(), start.elapsed().as_millis());
+}
+
+// Solves the built-in problems and checks each mate is found at its announced length
+fn solve_problems() {
+    let mut failures = 0;
+    for problem in MATE_PROBLEMS.iter() {
+        let chess_board = ChessBoard::from_fen(problem.fen).expect("problem positions are valid");
+        let mut mate_finder = MateFinder::new();
+        let result = mate_finder.find_mate(&chess_board, problem.moves);
+        let status = match &result {
+            MateResult::Mate { moves, .. } if *moves == problem.moves => "ok",
+            _ => { failures += 1; "FAILED" },
+        };
+        let line = match &result {
+            MateResult::Mate { line, .. } => format_line(line),
+            MateResult::NoMate => "no mate".to_string(),
+        };
+        println!("{:<6} mate in {} {:<30} {}", status, problem.moves, problem.name, line);
+    }
+    if failures > 0 {
+        panic!("{} problem(s) not solved", failures);
     }
+}
 
-    
+// Proof-number search on the given position, or on the built-in rook endings without --fen
+fn prove(fen: Option<&str>, search_limits: &SearchLimits) {
+    let fens = match fen {
+        Some(fen) => vec![fen],
+        None => KRK_POSITIONS.to_vec(),
+    };
+    let max_nodes = search_limits.nodes.map_or(DEFAULT_MAX_NODES, |nodes| nodes as usize);
+    let max_moves = search_limits.mate.unwrap_or(DEFAULT_MAX_MOVES);
+    for fen in fens {
+        let chess_board = load_board(Some(fen));
+        print_board(&chess_board);
+        let mut proof_number_search = ProofNumberSearch::new(max_nodes, max_moves);
+        let start = Instant::now();
+        match proof_number_search.prove(&chess_board) {
+            ProofResult::Proven { moves, line } => println!("mate in at most {}: {}", moves, format_line(&line)),
+            ProofResult::Disproven => println!("no forced mate in {} moves", max_moves),
+            ProofResult::Unknown => println!("unknown: more than {} nodes needed", max_nodes),
+        }
+        println!("expansions {} time {} ms", proof_number_search.expansions(), start.elapsed().as_millis());
+    }
+}
+
+fn parse_args() -> CliArgs {
+    let mut config = SearchConfig::default();
+    let mut limits = SearchLimits::default();
+    let mut command = None;
+    let mut fen = None;
+    let mut engine = "minimax".to_string();
+    let mut seed = None;
+    let mut skill = None;
+    let mut ponder = true;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fen" => fen = Some(args.next().expect("--fen expects a position")),
+            "--engine" => engine = args.next().expect("--engine expects a name"),
+            "--seed" => seed = Some(args.next().and_then(|n| n.parse().ok()).expect("--seed expects a number")),
+            "--skill" => skill = Some(args.next().and_then(|n| n.parse().ok()).expect("--skill expects a level from 0 to 20")),
+            "--no-ponder" => ponder = false,
+            "--deterministic" => config.deterministic = true,
+            "--threads" => config.threads = args.next().and_then(|n| n.parse().ok()).expect("--threads expects a number"),
+            "--hash" => config.hash_size_mb = args.next().and_then(|n| n.parse().ok()).expect("--hash expects a size in MB"),
+            "--no-null-move" => config.null_move = false,
+            "--no-lmr" => config.late_move_reductions = false,
+            "--no-futility" => config.futility_pruning = false,
+            "--no-razoring" => config.razoring = false,
+            "--no-delta" => config.delta_pruning = false,
+            "--no-check-extensions" => config.check_extensions = false,
+            "--no-single-reply-extensions" => config.single_reply_extensions = false,
+            "--recapture-extensions" => config.recapture_extensions = true,
+            "--no-aspiration" => config.aspiration_windows = false,
+            "--eval" => config.evaluator = create_evaluator(&args.next().expect("--eval expects a name")).unwrap_or_else(|error| panic!("{}", error)),
+            "--contempt" => config.contempt = args.next().and_then(|n| n.parse().ok()).expect("--contempt expects centipawns"),
+            "--multipv" => config.multi_pv = args.next().and_then(|n| n.parse().ok()).expect("--multipv expects a number"),
+            "--depth" => limits.depth = Some(args.next().and_then(|n| n.parse().ok()).expect("--depth expects a number")),
+            "--nodes" => limits.nodes = Some(args.next().and_then(|n| n.parse().ok()).expect("--nodes expects a number")),
+            "--mate" => limits.mate = Some(args.next().and_then(|n| n.parse().ok()).expect("--mate expects a number of moves")),
+            "--movetime" => limits.movetime = Some(Duration::from_millis(args.next().and_then(|n| n.parse().ok()).expect("--movetime expects milliseconds"))),
+            _ if command.is_none() && !arg.starts_with("--") => command = Some(arg),
+            _ => panic!("unknown argument {}", arg),
+        }
+    }
+    // Pondering would carry the table and the timing of the human's thinking into the next search
+    if config.deterministic {
+        ponder = false;
+    }
+    if limits.depth.is_none() && limits.nodes.is_none() && limits.movetime.is_none() {
+        limits.depth = Some(4);
+    }
+    CliArgs { command, fen, engine, seed, skill, ponder, config, limits }
+}
+
+fn format_score(score: Score) -> String {
+    match score {
+        Score::Mate(moves) if moves > 0 => format!("mate in {}", moves),
+        Score::Mate(moves) => format!("mated in {}", -moves),
+        Score::Cp(cp) => format!("{} cp", cp),
+    }
+}
+
+fn format_line(principal_variation: &[ChessMove]) -> String {
+    principal_variation.iter().map(format_move).collect::<Vec<String>>().join(" ")
+}
+
+fn print_search_result(search_result: &SearchResult) {
+    println!("depth {} seldepth {} score {} nodes {} nps {} time {} ms",
+        search_result.depth, search_result.seldepth, format_score(search_result.score),
+        search_result.nodes, search_result.nps, search_result.elapsed.as_millis());
+    if search_result.lines.len() > 1 {
+        for (index, line) in search_result.lines.iter().enumerate() {
+            println!("{:>2}. {:<12} {}", index + 1, format_score(line.score), format_line(&line.principal_variation));
+        }
+    }
+    else {
+        println!("pv {}", format_line(&search_result.principal_variation));
+    }
+    if !search_result.root_visits.is_empty() {
+        let visits: Vec<String> = search_result.root_visits.iter()
+            .map(|(chess_move, visits)| format!("{} {}", format_move(chess_move), visits))
+            .collect();
+        println!("visits {}", visits.join(", "));
+    }
+    let stats = &search_result.stats;
+    println!("null move {}/{} lmr {} (re-searched {}) aspiration fail low {} fail high {}",
+        stats.null_move_cutoffs, stats.null_move_tries, stats.lmr_reductions, stats.lmr_researches,
+        stats.aspiration_fail_lows, stats.aspiration_fail_highs);
+}
+
+fn format_position(position: &Position) -> String {
+    format!("{}{}", (b'a' + position.col as u8) as char, position.row + 1)
+}
+
+fn format_move(chess_move: &ChessMove) -> String {
+    match chess_move {
+        ChessMove::OrdinaryMove { from, to } => format!("{}{}", format_position(from), format_position(to)),
+        ChessMove::Castle { king, rook } => if rook.col < king.col { "O-O-O".to_string() } else { "O-O".to_string() },
+    }
 }
 
 fn print_board(chess_board: &ChessBoard) {
     println!("  a b c d e f g h"); // En-tête pour les colonnes
-    for (i, row) in ChessBoard::get_board(&chess_board).iter().enumerate().rev() {
+    for (i, row) in ChessBoard::get_board(chess_board).iter().enumerate().rev() {
         print!("{} ", i+1); // Affiche le numéro de rangée avant chaque ligne, en commençant par 8
         for piece_option in row {
             let piece_symbol = match piece_option {
@@ -0,0 +1,153 @@
+use crate::chess_board::{ChessBoard, ChessMove, PartyState};
+use std::collections::HashMap;
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum MateResult {
+    // Shortest forced mate, in moves of the side to move. The line alternates attacker moves and
+    // the defender replies that hold out the longest, and ends with the mating move.
+    Mate { moves: usize, line: Vec<ChessMove> },
+    NoMate,
+}
+
+pub struct MateProblem {
+    pub name : &'static str,
+    pub fen : &'static str,
+    pub moves : usize,
+}
+
+// Classic problems the solver must prove exactly at their announced length
+pub const MATE_PROBLEMS: [MateProblem; 4] = [
+    MateProblem { name: "Morphy, opera game 1858", fen: "4kb1r/p2n1ppp/4q3/4p1B1/4P3/1Q6/PPP2PPP/2KR4 w - - 1 17", moves: 2 },
+    MateProblem { name: "queen sacrifice on d8", fen: "r1b2k1r/ppp1bppp/8/1B1Q4/5q2/2P5/PPP2PPP/R3R1K1 w - - 1 1", moves: 2 },
+    MateProblem { name: "back rank double sacrifice", fen: "2kr4/ppp5/8/8/8/8/5PPP/1Q1R2K1 w - - 0 1", moves: 3 },
+    MateProblem { name: "Philidor smothered mate", fen: "5rk1/5Npp/8/8/2Q5/8/6PP/6K1 w - - 0 1", moves: 3 },
+];
+
+// AND/OR search: the attacker needs one move after which every defence still loses, the
+// defender refutes the mate with a single reply that escapes. Unlike minimax, no evaluation is
+// involved, so a proven mate is a proof and a refutation holds against every defence.
+#[derive(Default)]
+pub struct MateFinder {
+    nodes : u64,
+    // Positions (attacker to move) known to have no mate within that many moves
+    refuted : HashMap<u64, usize>,
+}
+
+impl MateFinder {
+    pub fn new() -> Self {
+        MateFinder::default()
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    // Proves or refutes a mate in at most `max_moves` moves for the side to move
+    pub fn find_mate(&mut self, chess_board: &ChessBoard, max_moves: usize) -> MateResult {
+        let moves = ChessBoard::get_party_moves(chess_board);
+        for depth in 1..=max_moves {
+            if let Some(line) = self.attack(chess_board, &moves, depth) {
+                return MateResult::Mate { moves: depth, line };
+            }
+        }
+        MateResult::NoMate
+    }
+
+    // Mating line within `depth` moves, or None if the defender always escapes
+    fn attack(&mut self, chess_board: &ChessBoard, moves: &[ChessMove], depth: usize) -> Option<Vec<ChessMove>> {
+        let hash = chess_board.get_hash();
+        if self.refuted.get(&hash).is_some_and(|&refuted| refuted >= depth) {
+            return None;
+        }
+        let mut candidates = vec![];
+        for chess_move in moves.iter() {
+            // Every mate is a check: quiet moves cannot end the line
+            if depth == 1 && !chess_board.gives_check(chess_move) {
+                continue;
+            }
+            self.nodes += 1;
+            let mut child = chess_board.clone();
+            let replies = child.update_board(chess_move.clone());
+            match ChessBoard::get_party_state(&child) {
+                PartyState::CheckMate => return Some(vec![chess_move.clone()]),
+                PartyState::Pat => (),
+                state => {
+                    if depth > 1 {
+                        candidates.push((state == PartyState::Check, chess_move.clone(), child, replies));
+                    }
+                }
+            }
+        }
+        // Checks first, then the moves leaving the fewest replies
+        candidates.sort_by_key(|(is_check, _, _, replies)| (!is_check, replies.len()));
+        for (_, chess_move, child, replies) in candidates {
+            if let Some(mut line) = self.defend(&child, &replies, depth - 1) {
+                line.insert(0, chess_move);
+                return Some(line);
+            }
+        }
+        self.refuted.insert(hash, depth);
+        None
+    }
+
+    // Line after the most stubborn reply when every reply is mated within `depth` moves
+    fn defend(&mut self, chess_board: &ChessBoard, replies: &[ChessMove], depth: usize) -> Option<Vec<ChessMove>> {
+        let mut longest: Option<(usize, Vec<ChessMove>)> = None;
+        for reply in replies.iter() {
+            self.nodes += 1;
+            let mut child = chess_board.clone();
+            let moves = child.update_board(reply.clone());
+            if moves.is_empty() {
+                // The attacker is mated or stalemated
+                return None;
+            }
+            let (length, line) = (1..=depth)
+                .find_map(|length| self.attack(&child, &moves, length).map(|line| (length, line)))?;
+            if longest.as_ref().is_none_or(|(longest_length, _)| length > *longest_length) {
+                let mut line = line;
+                line.insert(0, reply.clone());
+                longest = Some((length, line));
+            }
+        }
+        longest.map(|(_, line)| line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every move of the line must be legal where it is played, and the last one must mate
+    fn assert_mating_line(fen: &str, line: &[ChessMove]) {
+        let mut chess_board = ChessBoard::from_fen(fen).unwrap();
+        let mut moves = ChessBoard::get_party_moves(&chess_board);
+        for chess_move in line.iter() {
+            assert!(moves.contains(chess_move), "{:?} is not legal in {}", chess_move, fen);
+            moves = chess_board.update_board(chess_move.clone());
+        }
+        assert_eq!(ChessBoard::get_party_state(&chess_board), PartyState::CheckMate, "{}", fen);
+    }
+
+    #[test]
+    fn solves_every_problem_at_its_announced_length() {
+        for problem in MATE_PROBLEMS.iter() {
+            let chess_board = ChessBoard::from_fen(problem.fen).unwrap();
+            match MateFinder::new().find_mate(&chess_board, problem.moves) {
+                MateResult::Mate { moves, line } => {
+                    assert_eq!(moves, problem.moves, "{}", problem.name);
+                    assert_eq!(line.len(), 2 * moves - 1, "{}", problem.name);
+                    assert_mating_line(problem.fen, &line);
+                },
+                MateResult::NoMate => panic!("no mate found for {}", problem.name),
+            }
+        }
+    }
+
+    #[test]
+    fn finds_no_shorter_mate() {
+        for problem in MATE_PROBLEMS.iter() {
+            let chess_board = ChessBoard::from_fen(problem.fen).unwrap();
+            assert_eq!(MateFinder::new().find_mate(&chess_board, problem.moves - 1), MateResult::NoMate, "{}", problem.name);
+        }
+    }
+}
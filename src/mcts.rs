@@ -0,0 +1,296 @@
+use crate::chess_board::{ChessBoard, ChessMove, PartyState};
+use crate::evaluator::{Evaluator, PositionalEvaluator};
+use crate::minimax::{eval_function, nodes_per_second, Score, SearchResult, SearchStats, MATE_BOUND};
+use crate::rng::Rng;
+use crate::search_limits::{SearchClock, SearchLimits, StopSignal};
+use crate::searcher::Searcher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+// Playouts run when the limits give neither a node count nor a time
+const DEFAULT_ITERATIONS: u64 = 2000;
+// Scale of the logistic curve turning centipawns into a winning probability
+const CP_PER_WIN_ODDS: f64 = 400.0;
+// Share of the eval-guided playout moves still drawn at random, so that playouts stay varied
+const RANDOM_MOVE_PERCENT: usize = 20;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Playout {
+    // Uniform random moves
+    Random,
+    // Epsilon-greedy: usually the move with the best one-ply evaluation, sometimes a random one
+    EvalGuided,
+}
+
+#[derive(Clone,Debug)]
+pub struct MctsConfig {
+    // Independent trees merged at the root (root parallelism)
+    pub threads : usize,
+    // UCT exploration constant
+    pub exploration : f64,
+    pub playout : Playout,
+    // Plies after which a playout is stopped and scored by the evaluation
+    pub playout_depth : usize,
+    // Scores the playouts cut at `playout_depth`
+    pub evaluator : Arc<dyn Evaluator>,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            threads : 1,
+            exploration : std::f64::consts::SQRT_2,
+            playout : Playout::Random,
+            playout_depth : 40,
+            evaluator : Arc::new(PositionalEvaluator::default()),
+        }
+    }
+}
+
+// UCT player: each iteration walks down the tree by upper confidence bound, adds one node and
+// scores it with a playout. The limits count playouts as nodes.
+pub struct Mcts {
+    config : MctsConfig,
+    rng : Rng,
+}
+
+struct MctsNode {
+    chess_move : Option<ChessMove>,
+    chess_board : ChessBoard,
+    parent : Option<usize>,
+    children : Vec<usize>,
+    untried : Vec<ChessMove>,
+    visits : u64,
+    // Sum of the rewards of the side that played `chess_move`
+    reward : f64,
+}
+
+struct MctsTree {
+    nodes : Vec<MctsNode>,
+    max_depth : usize,
+}
+
+impl Mcts {
+    pub fn new(config: MctsConfig, rng: Rng) -> Self {
+        Mcts { config, rng }
+    }
+}
+
+impl Searcher for Mcts {
+    fn search(&mut self, chess_board: &ChessBoard, limits: SearchLimits) -> SearchResult {
+        let limits = if limits.nodes.is_none() && limits.movetime.is_none() && limits.wtime.is_none() && limits.btime.is_none() {
+            SearchLimits { nodes: Some(DEFAULT_ITERATIONS), ..limits }
+        } else {
+            limits
+        };
+        let clock = SearchClock::new(&limits, ChessBoard::get_turn(chess_board));
+        let stop = StopSignal::new();
+        let iterations = AtomicU64::new(0);
+        let seeds: Vec<u64> = (0..self.config.threads.max(1)).map(|_| self.rng.next_u64()).collect();
+        let trees: Vec<MctsTree> = thread::scope(|scope| {
+            let handles: Vec<_> = seeds.into_iter()
+                .map(|seed| {
+                    let (config, clock, stop, iterations) = (&self.config, &clock, &stop, &iterations);
+                    scope.spawn(move || {
+                        let mut tree = MctsTree::new(chess_board);
+                        let mut rng = Rng::new(seed);
+                        while !stop.is_stopped() && !clock.hard_limit_reached(iterations.fetch_add(1, Ordering::Relaxed)) {
+                            tree.iterate(config, &mut rng);
+                        }
+                        stop.stop();
+                        tree
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().expect("mcts thread panicked")).collect()
+        });
+        merge_trees(&trees, &clock)
+    }
+}
+
+impl MctsTree {
+    fn new(chess_board: &ChessBoard) -> Self {
+        let root = MctsNode {
+            chess_move : None,
+            chess_board : chess_board.clone(),
+            parent : None,
+            children : vec![],
+            untried : ChessBoard::get_party_moves(chess_board),
+            visits : 0,
+            reward : 0.0,
+        };
+        MctsTree { nodes: vec![root], max_depth: 0 }
+    }
+
+    fn iterate(&mut self, config: &MctsConfig, rng: &mut Rng) {
+        let mut index = 0;
+        let mut depth = 0;
+        while self.nodes[index].untried.is_empty() && !self.nodes[index].children.is_empty() {
+            index = self.select_child(index, config.exploration);
+            depth += 1;
+        }
+        if !self.nodes[index].untried.is_empty() {
+            let untried = &mut self.nodes[index].untried;
+            let chess_move = untried.swap_remove(rng.below(untried.len()));
+            let mut chess_board = self.nodes[index].chess_board.clone();
+            let moves = chess_board.update_board(chess_move.clone());
+            self.nodes.push(MctsNode {
+                chess_move : Some(chess_move),
+                chess_board,
+                parent : Some(index),
+                children : vec![],
+                untried : moves,
+                visits : 0,
+                reward : 0.0,
+            });
+            let child = self.nodes.len() - 1;
+            self.nodes[index].children.push(child);
+            index = child;
+            depth += 1;
+        }
+        self.max_depth = self.max_depth.max(depth);
+        let node = &self.nodes[index];
+        let reward = playout(&node.chess_board, &node.untried, config, rng);
+        // The reward is for the side to move at the leaf, each node keeps the one of its mover
+        let mut reward = 1.0 - reward;
+        let mut current = Some(index);
+        while let Some(index) = current {
+            self.nodes[index].visits += 1;
+            self.nodes[index].reward += reward;
+            reward = 1.0 - reward;
+            current = self.nodes[index].parent;
+        }
+    }
+
+    fn select_child(&self, index: usize, exploration: f64) -> usize {
+        let log_visits = (self.nodes[index].visits as f64).ln();
+        let uct = |child: usize| {
+            let node = &self.nodes[child];
+            let visits = node.visits as f64;
+            node.reward / visits + exploration * (log_visits / visits).sqrt()
+        };
+        *self.nodes[index].children.iter()
+            .max_by(|&&a, &&b| uct(a).total_cmp(&uct(b)))
+            .expect("a fully expanded node has children")
+    }
+
+    fn most_visited_child(&self, index: usize) -> Option<usize> {
+        self.nodes[index].children.iter().copied().max_by_key(|&child| self.nodes[child].visits)
+    }
+}
+
+// Reward in [0, 1] for the side to move: 1 for a win, 0.5 for a draw
+fn playout(chess_board: &ChessBoard, moves: &[ChessMove], config: &MctsConfig, rng: &mut Rng) -> f64 {
+    let mut chess_board = chess_board.clone();
+    let mut moves = moves.to_vec();
+    let mut ply = 0;
+    let reward = loop {
+        if moves.is_empty() {
+            break match ChessBoard::get_party_state(&chess_board) {
+                PartyState::CheckMate => 0.0,
+                _ => 0.5,
+            };
+        }
+        if ply >= config.playout_depth {
+            break win_probability(eval_function(&chess_board, config.evaluator.as_ref()));
+        }
+        let index = match config.playout {
+            Playout::Random => rng.below(moves.len()),
+            Playout::EvalGuided => guided_choice(&chess_board, &moves, config.evaluator.as_ref(), rng),
+        };
+        moves = chess_board.update_board(moves.swap_remove(index));
+        ply += 1;
+    };
+    if ply % 2 == 0 { reward } else { 1.0 - reward }
+}
+
+// Move whose resulting position the evaluator likes best for the mover, or a random move
+fn guided_choice(chess_board: &ChessBoard, moves: &[ChessMove], evaluator: &dyn Evaluator, rng: &mut Rng) -> usize {
+    if rng.below(100) < RANDOM_MOVE_PERCENT {
+        return rng.below(moves.len());
+    }
+    // The evaluation is from the side to move after the move, the opponent
+    (0..moves.len())
+        .max_by_key(|&index| -evaluator.evaluate(&chess_board.preview_move(&moves[index])))
+        .expect("a playout only continues with legal moves")
+}
+
+fn win_probability(cp: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-cp as f64 / CP_PER_WIN_ODDS))
+}
+
+fn centipawns(win_probability: f64) -> i32 {
+    let p = win_probability.clamp(0.001, 0.999);
+    ((CP_PER_WIN_ODDS * (p / (1.0 - p)).log10()) as i32).clamp(-MATE_BOUND + 1, MATE_BOUND - 1)
+}
+
+// Root parallelism: the visits and rewards of every root move are summed over the trees
+fn merge_trees(trees: &[MctsTree], clock: &SearchClock) -> SearchResult {
+    let mut root_moves: Vec<(ChessMove, u64, f64)> = vec![];
+    for tree in trees.iter() {
+        for &child in tree.nodes[0].children.iter() {
+            let node = &tree.nodes[child];
+            let chess_move = node.chess_move.clone().expect("only the root has no move");
+            match root_moves.iter_mut().find(|(root_move, _, _)| *root_move == chess_move) {
+                Some(entry) => {
+                    entry.1 += node.visits;
+                    entry.2 += node.reward;
+                },
+                None => root_moves.push((chess_move, node.visits, node.reward)),
+            }
+        }
+    }
+    root_moves.sort_by_key(|(_, visits, _)| std::cmp::Reverse(*visits));
+    let best = root_moves.first().cloned();
+    // Main line from the tree that explored the best move the most
+    let mut principal_variation = vec![];
+    if let Some((best_move, _, _)) = &best {
+        let tree = trees.iter()
+            .max_by_key(|tree| tree.nodes[0].children.iter()
+                .filter(|&&child| tree.nodes[child].chess_move.as_ref() == Some(best_move))
+                .map(|&child| tree.nodes[child].visits)
+                .sum::<u64>())
+            .expect("there is at least one tree");
+        let mut index = 0;
+        while let Some(child) = tree.most_visited_child(index) {
+            principal_variation.push(tree.nodes[child].chess_move.clone().expect("only the root has no move"));
+            index = child;
+        }
+    }
+    let nodes = trees.iter().map(|tree| tree.nodes[0].visits).sum();
+    let elapsed = clock.elapsed();
+    SearchResult {
+        best_move : best.as_ref().map(|(chess_move, _, _)| chess_move.clone()),
+        score : Score::Cp(best.as_ref().map_or(0, |(_, visits, reward)| centipawns(reward / *visits as f64))),
+        principal_variation,
+        lines : vec![],
+        depth : trees.iter().map(|tree| tree.max_depth).max().unwrap_or(0),
+        seldepth : trees.iter().map(|tree| tree.max_depth).max().unwrap_or(0),
+        nodes,
+        elapsed,
+        nps : nodes_per_second(nodes, elapsed),
+        stats : SearchStats::default(),
+        root_visits : root_moves.into_iter().map(|(chess_move, visits, _)| (chess_move, visits)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::Position;
+    use crate::evaluator::MaterialEvaluator;
+
+    #[test]
+    fn eval_guided_playouts_follow_the_evaluator() {
+        // exd5 wins the queen, a random pick would almost never choose it
+        let chess_board = ChessBoard::from_fen("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let moves = ChessBoard::get_party_moves(&chess_board);
+        let capture = ChessMove::OrdinaryMove { from: Position { row: 3, col: 4 }, to: Position { row: 4, col: 3 } };
+        let mut rng = Rng::new(3);
+        let captures = (0..200)
+            .filter(|_| moves[guided_choice(&chess_board, &moves, &MaterialEvaluator, &mut rng)] == capture)
+            .count();
+        assert!(captures >= 200 * (100 - RANDOM_MOVE_PERCENT) / 100 - 20, "{} captures", captures);
+    }
+}
@@ -1,152 +1,992 @@
 
 
-
-
-use crate::chess_board::{ChessBoard, ChessMove, Color, PartyState, PieceType};
-use std::sync::mpsc;
+use crate::attacks::side;
+use crate::chess_board::{ChessBoard, ChessMove, Color, PartyState, PieceType, Position};
+use crate::evaluator::Evaluator;
+use crate::observer::{SearchInfo, SearchObserver, PROGRESS_INTERVAL};
+use crate::search_limits::{PonderSignal, SearchClock, SearchLimits, StopSignal};
+use crate::search_pool::SearchConfig;
+use crate::transposition::{Bound, TranspositionTable};
+use crate::zobrist;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::fmt;
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
+
+pub const MAX_DEPTH: usize = 64;
+// Hard bound on the distance from the root, whatever the extensions
+const MAX_PLY: usize = 2 * MAX_DEPTH;
+pub const INFINITY: i32 = 32000;
+// Being mated at ply p scores -(MATE - p), so a shorter mate is always preferred
+pub const MATE: i32 = 30000;
+pub const MATE_BOUND: i32 = MATE - 2 * MAX_DEPTH as i32;
+// Nodes searched between two looks at the clock and the shared node counter. Under a node limit
+// every node is counted at once, so that budgets smaller than the interval are kept.
+const CHECK_INTERVAL: u64 = 1024;
+// Depth reduction of the null-move search
+const NULL_MOVE_REDUCTION: usize = 2;
+// Moves searched at full depth before late move reductions kick in
+const LMR_FULL_DEPTH_MOVES: usize = 3;
+const LMR_MIN_DEPTH: usize = 3;
+// Shallower iterations are cheap and too unstable for a narrow window
+const ASPIRATION_MIN_DEPTH: usize = 4;
 
 #[derive(Clone,Debug,Copy)]
 struct MinimaxInfo {
     depth : usize,
     max_depth : usize,
-    color : Color,
     alpha : i32,
     beta : i32,
+    // Two null moves in a row would just give the move back
+    allow_null : bool,
+    // Plies already added to this line by extensions
+    extensions : usize,
+    // Square of the capture that led to this node, for recapture extensions
+    last_capture : Option<Position>,
 }
 
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Score {
+    // Centipawns from the side to move point of view
+    Cp(i32),
+    // Mate in that many moves, negative when the side to move gets mated
+    Mate(i32),
+}
 
+impl Score {
+    // Search value of the score, so that scores can be compared and subtracted
+    pub fn to_value(self) -> i32 {
+        match self {
+            Score::Cp(cp) => cp,
+            Score::Mate(moves) if moves > 0 => MATE - (2 * moves - 1),
+            Score::Mate(moves) => -(MATE + 2 * moves),
+        }
+    }
 
-pub fn start_minimax(chess_board: &ChessBoard) -> Option<ChessMove>{
-    let (tx, rx) = mpsc::channel();
-    let mut threads = vec![];
-    let all_moves = ChessBoard::get_party_moves(chess_board);
-    println!("all moves : {:?} turn {:?} ",all_moves,ChessBoard::get_turn(&chess_board));
-    let chunk_size = all_moves.len()/4; 
-    let minimax_info = MinimaxInfo {
-        max_depth : 4,
-        color : ChessBoard::get_turn(chess_board),
-        depth : 0,
-        alpha : i32::MIN,
-        beta : i32::MAX,
-    };
-    for chunk in all_moves.chunks(chunk_size) { 
-        
-        let chess_board_clone = chess_board.clone();
-        let moves_chunk = chunk.to_vec();
-        let tx_clone = tx.clone();
-        let minimax_info_clone = minimax_info.clone();
-        let thread = thread::spawn(move || {
-            println!("[thread] first move : {:?}",moves_chunk[0]);
-            let (score, minimax_move) = minimax(chess_board_clone, moves_chunk, minimax_info_clone);
-            tx_clone.send((score, minimax_move)).unwrap();
-        });
-        threads.push(thread);
-            
+    pub fn from_value(value: i32) -> Self {
+        if value >= MATE_BOUND {
+            Score::Mate((MATE - value + 1) / 2)
+        } else if value <= -MATE_BOUND {
+            Score::Mate(-(MATE + value) / 2)
+        } else {
+            Score::Cp(value)
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::Cp(cp) => write!(f, "cp {}", cp),
+            Score::Mate(moves) => write!(f, "mate {}", moves),
+        }
     }
+}
 
-    drop(tx);
+// Pruning margins in centipawns, indexed by the remaining depth
+#[derive(Clone,Debug)]
+pub struct SearchParams {
+    pub futility_margins : [i32; 4],
+    pub razoring_margins : [i32; 3],
+    // Safety margin added to the captured piece value before a capture is skipped in quiescence
+    pub delta_margin : i32,
+    // Most plies extensions may add to a single line
+    pub max_extensions : usize,
+    // Half-width of the first aspiration window
+    pub aspiration_window : i32,
+    // Largest random offset added to the evaluation, used to weaken the lower skill levels
+    pub eval_noise : i32,
+    pub noise_seed : u64,
+}
 
-    let best_move = rx.into_iter()
-        .max_by_key(|&(score, _)|  {println!("found,{:?}",score);score})
-        .map(|(_, best_move)| best_move)
-        .expect("Aucun coup valide trouvé");
-    best_move
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            futility_margins : [0, 200, 350, 500],
+            razoring_margins : [0, 300, 550],
+            delta_margin : 200,
+            max_extensions : 8,
+            aspiration_window : 50,
+            eval_noise : 0,
+            noise_seed : 0,
+        }
+    }
 }
 
-fn minimax(chess_board: ChessBoard,chess_moves: Vec<ChessMove>, mut minimax_info: MinimaxInfo) -> (i32,Option<ChessMove>) {
-    let mut best_score: i32 = 0;
-    let mut best_move = None;
-    if minimax_info.depth == minimax_info.max_depth || chess_moves.len() == 0  {
-        let score = eval_function(chess_board, minimax_info);
-        return (score,None);
+#[derive(Clone,Debug,Default)]
+pub struct SearchStats {
+    pub quiescence_nodes : u64,
+    pub null_move_tries : u64,
+    pub null_move_cutoffs : u64,
+    pub lmr_reductions : u64,
+    pub lmr_researches : u64,
+    pub futility_prunes : u64,
+    pub razoring_cutoffs : u64,
+    pub delta_prunes : u64,
+    pub extensions : u64,
+    pub aspiration_fail_lows : u64,
+    pub aspiration_fail_highs : u64,
+}
+
+impl SearchStats {
+    pub fn add(&mut self, other: &SearchStats) {
+        self.quiescence_nodes += other.quiescence_nodes;
+        self.null_move_tries += other.null_move_tries;
+        self.null_move_cutoffs += other.null_move_cutoffs;
+        self.lmr_reductions += other.lmr_reductions;
+        self.lmr_researches += other.lmr_researches;
+        self.futility_prunes += other.futility_prunes;
+        self.razoring_cutoffs += other.razoring_cutoffs;
+        self.delta_prunes += other.delta_prunes;
+        self.extensions += other.extensions;
+        self.aspiration_fail_lows += other.aspiration_fail_lows;
+        self.aspiration_fail_highs += other.aspiration_fail_highs;
     }
-    minimax_info.depth += 1;
-    if ChessBoard::get_turn(&chess_board) == minimax_info.color {
-        
-        best_score = i32::MIN;
-        for chess_move in chess_moves.iter() {
-            
+}
+
+#[derive(Clone,Debug)]
+pub struct PvLine {
+    pub score : Score,
+    pub principal_variation : Vec<ChessMove>,
+}
+
+#[derive(Clone,Debug)]
+pub struct SearchResult {
+    pub best_move : Option<ChessMove>,
+    pub score : Score,
+    pub principal_variation : Vec<ChessMove>,
+    // The `multi_pv` best root moves, ordered by score; the first one is the main line
+    pub lines : Vec<PvLine>,
+    pub depth : usize,
+    pub seldepth : usize,
+    pub nodes : u64,
+    pub elapsed : Duration,
+    pub nps : u64,
+    pub stats : SearchStats,
+    // Playouts through each root move, for the searchers that count them (MCTS)
+    pub root_visits : Vec<(ChessMove, u64)>,
+}
+
+#[derive(Clone,Debug)]
+pub struct ThreadReport {
+    pub id : usize,
+    pub best_move : Option<ChessMove>,
+    pub score : i32,
+    pub principal_variation : Vec<ChessMove>,
+    // Score and principal variation of each MultiPV line, best first
+    pub lines : Vec<(i32, Vec<ChessMove>)>,
+    pub depth : usize,
+    pub seldepth : usize,
+    pub nodes : u64,
+    pub stats : SearchStats,
+}
+
+// One Lazy SMP worker: every thread searches the whole tree, they only cooperate through the
+// shared transposition table. Helpers (id > 0) start at a different depth and root order so
+// they fill the table with entries the main thread has not reached yet.
+pub struct SearchThread {
+    id : usize,
+    tt : Arc<TranspositionTable>,
+    config : SearchConfig,
+    stop : StopSignal,
+    ponder : PonderSignal,
+    clock : SearchClock,
+    // Nodes of all threads, for the node limit
+    shared_nodes : Arc<AtomicU64>,
+    nodes : u64,
+    seldepth : usize,
+    stats : SearchStats,
+    // Quiet moves that caused a cutoff, two per ply
+    killers : Vec<[Option<ChessMove>; 2]>,
+    // Cutoff counts of quiet moves, indexed by color, from and to square
+    history : Vec<[[i32; 64]; 64]>,
+    // Side the engine plays, for the contempt
+    root_turn : Color,
+    observer : Option<Arc<dyn SearchObserver>>,
+    // MultiPV line being searched, best move events are only sent for the first one
+    pv_index : usize,
+    last_progress : Duration,
+    // Deepest iteration searched to the end. The limits only stop the search once the first one
+    // is done, so every root move has a real score.
+    completed_depth : usize,
+    // Nodes added to the shared count at once, CHECK_INTERVAL or 1 under a node limit
+    check_interval : u64,
+}
+
+impl SearchThread {
+    pub fn new(id: usize, tt: Arc<TranspositionTable>, config: SearchConfig, stop: StopSignal, ponder: PonderSignal, clock: SearchClock, shared_nodes: Arc<AtomicU64>) -> Self {
+        SearchThread {
+            id,
+            tt,
+            config,
+            stop,
+            ponder,
+            clock,
+            shared_nodes,
+            nodes : 0,
+            seldepth : 0,
+            stats : SearchStats::default(),
+            killers : vec![[None, None]; MAX_PLY + 1],
+            history : vec![[[0; 64]; 64]; 2],
+            root_turn : Color::White,
+            observer : None,
+            pv_index : 0,
+            last_progress : Duration::ZERO,
+            completed_depth : 0,
+            check_interval : if clock.has_node_limit() { 1 } else { CHECK_INTERVAL },
+        }
+    }
+
+    pub fn set_observer(&mut self, observer: Arc<dyn SearchObserver>) {
+        self.observer = Some(observer);
+    }
+
+    pub fn iterative_deepening(&mut self, chess_board: &ChessBoard, limits: &SearchLimits) -> ThreadReport {
+        self.root_turn = ChessBoard::get_turn(chess_board);
+        self.completed_depth = 0;
+        let mut root_moves = ChessBoard::get_party_moves(chess_board);
+        let mut report = ThreadReport {
+            id : self.id,
+            best_move : None,
+            score : 0,
+            principal_variation : vec![],
+            lines : vec![],
+            depth : 0,
+            seldepth : 0,
+            nodes : 0,
+            stats : SearchStats::default(),
+        };
+        if root_moves.is_empty() {
+            return report;
+        }
+        // A stop from the front-end before the first iteration completes still yields a legal move
+        report.best_move = Some(root_moves[0].clone());
+        report.principal_variation = vec![root_moves[0].clone()];
+        report.lines = vec![(0, report.principal_variation.clone())];
+        // Only the main thread produces the extra lines, helpers keep feeding the table
+        let multi_pv = if self.id == 0 { self.config.multi_pv.clamp(1, root_moves.len()) } else { 1 };
+
+        let mut max_depth = limits.depth.unwrap_or(MAX_DEPTH);
+        if let Some(mate) = limits.mate {
+            max_depth = max_depth.min((2 * mate).saturating_sub(1).max(1));
+        }
+        let (first_depth, last_depth) = if self.id == 0 {
+            (1, max_depth.clamp(1, MAX_DEPTH))
+        } else {
+            let len = root_moves.len();
+            root_moves.rotate_left(self.id % len);
+            // Helpers stop at the same depth limit, a deeper helper would override the requested depth
+            (1 + self.id % 2, max_depth.clamp(1, MAX_DEPTH))
+        };
+
+        for depth in first_depth..=last_depth {
+            // Line k is the best move among the root moves not already chosen by lines 0..k
+            let mut lines = vec![];
+            for pv_index in 0..multi_pv {
+                self.pv_index = pv_index;
+                let previous_score = report.lines.get(pv_index).map_or(report.score, |line| line.0);
+                match self.aspiration_search(chess_board, &mut root_moves[pv_index..], depth, previous_score) {
+                    Some(line) => lines.push(line),
+                    None => break,
+                }
+                if self.stopped() {
+                    break;
+                }
+            }
+            // An interrupted iteration is never reported: its depth would beat the completed
+            // iterations of the other threads with a move that was not searched to the end
+            if lines.len() == multi_pv && !self.stopped() {
+                lines.sort_by_key(|line| std::cmp::Reverse(line.0));
+                for (index, line) in lines.iter().enumerate() {
+                    root_moves[index] = line.1[0].clone();
+                }
+                report.best_move = lines[0].1.first().cloned();
+                report.score = lines[0].0;
+                report.principal_variation = lines[0].1.clone();
+                report.lines = lines;
+                report.depth = depth;
+                report.seldepth = self.seldepth;
+                self.completed_depth = depth;
+                self.report_depth(&report);
+            }
+            if self.stopped() {
+                break;
+            }
+            let mate_found = limits.mate.is_some_and(|mate| {
+                matches!(Score::from_value(report.score), Score::Mate(moves) if moves > 0 && moves as usize <= mate)
+            });
+            if self.id == 0 && !self.ponder.is_pondering() && (self.clock.soft_limit_reached() || mate_found) {
+                break;
+            }
+        }
+        if self.id == 0 {
+            // An infinite or ponder search only returns once the interface stops it (or on
+            // ponderhit)
+            while !self.stopped() && (limits.infinite || self.ponder.is_pondering()) {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        report.nodes = self.nodes;
+        report.stats = self.stats.clone();
+        report
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.is_stopped()
+    }
+
+    fn check_limits(&mut self) {
+        if !self.nodes.is_multiple_of(self.check_interval) {
+            return;
+        }
+        let shared_nodes = self.shared_nodes.fetch_add(self.check_interval, Ordering::Relaxed) + self.check_interval;
+        if self.id == 0 && self.completed_depth > 0 && !self.ponder.is_pondering() && self.clock.hard_limit_reached(shared_nodes) {
+            self.stop.stop();
+        }
+        if let Some(observer) = &self.observer {
+            let elapsed = self.clock.elapsed();
+            if elapsed >= self.last_progress + PROGRESS_INTERVAL {
+                self.last_progress = elapsed;
+                observer.on_progress(shared_nodes, nodes_per_second(shared_nodes, elapsed), elapsed);
+            }
+        }
+    }
+
+    fn report_depth(&self, report: &ThreadReport) {
+        let Some(observer) = &self.observer else {
+            return;
+        };
+        // The shared count moves by check_interval, the main thread adds what it has not yet counted
+        let nodes = self.shared_nodes.load(Ordering::Relaxed) + self.nodes % self.check_interval;
+        let elapsed = self.clock.elapsed();
+        for (index, (score, principal_variation)) in report.lines.iter().enumerate() {
+            observer.on_depth(&SearchInfo {
+                depth : report.depth,
+                seldepth : report.seldepth,
+                multi_pv : index + 1,
+                score : Score::from_value(*score),
+                nodes,
+                nps : nodes_per_second(nodes, elapsed),
+                elapsed,
+                principal_variation : principal_variation.clone(),
+            });
+        }
+    }
+
+    // Searches the iteration in a window centred on the previous score, and widens it on the
+    // failing side until the score falls inside. Mate scores and shallow depths use a full window.
+    fn aspiration_search(&mut self, chess_board: &ChessBoard, root_moves: &mut [ChessMove], depth: usize, previous_score: i32) -> Option<(i32, Vec<ChessMove>)> {
+        let mut delta = self.config.params.aspiration_window;
+        let (mut alpha, mut beta) = if self.config.aspiration_windows && depth >= ASPIRATION_MIN_DEPTH && previous_score.abs() < MATE_BOUND {
+            ((previous_score - delta).max(-INFINITY), (previous_score + delta).min(INFINITY))
+        } else {
+            (-INFINITY, INFINITY)
+        };
+        loop {
+            let result = self.search_root(chess_board, root_moves, depth, alpha, beta);
+            let (score, _) = result.as_ref()?;
+            let score = *score;
+            if self.stopped() {
+                // A partial fail-low says nothing about the best move, keep the previous iteration
+                return if score > alpha { result } else { None };
+            }
+            if score <= alpha && alpha > -INFINITY {
+                self.stats.aspiration_fail_lows += 1;
+                beta = (alpha + beta) / 2;
+                alpha = (score - delta).max(-INFINITY);
+            } else if score >= beta && beta < INFINITY {
+                self.stats.aspiration_fail_highs += 1;
+                beta = (score + delta).min(INFINITY);
+            } else {
+                return result;
+            }
+            delta *= 2;
+        }
+    }
+
+    // Returns the score and principal variation of the iteration (the first move of the
+    // variation is the best root move), or of the part of it searched
+    // before a stop.
+    fn search_root(&mut self, chess_board: &ChessBoard, root_moves: &mut [ChessMove], max_depth: usize, mut alpha: i32, beta: i32) -> Option<(i32, Vec<ChessMove>)> {
+        let window_alpha = alpha;
+        let mut best: Option<(i32, usize)> = None;
+        let mut principal_variation = vec![];
+        for (index, chess_move) in root_moves.iter().enumerate() {
+            if let Some(observer) = &self.observer {
+                observer.on_current_move(max_depth, chess_move, self.pv_index + index + 1);
+            }
+            let mut chess_board_clone = chess_board.clone();
+            let new_all_moves = ChessBoard::update_board(&mut chess_board_clone, chess_move.clone());
+            let minimax_info = MinimaxInfo {
+                depth : 1,
+                max_depth,
+                alpha : -beta,
+                beta : -alpha,
+                allow_null : true,
+                extensions : 0,
+                last_capture : capture_square(chess_board, chess_move),
+            };
+            let mut child_pv = vec![];
+            let score = -self.minimax(&chess_board_clone, new_all_moves, minimax_info, &mut child_pv);
+            if self.stopped() {
+                break;
+            }
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, index));
+                alpha = alpha.max(score);
+                principal_variation = vec![chess_move.clone()];
+                principal_variation.extend(child_pv);
+                // Under the window the score is only a bound, the move is not known to be best
+                if let Some(observer) = self.observer.as_ref().filter(|_| self.pv_index == 0 && score > window_alpha) {
+                    observer.on_best_move(max_depth, chess_move, Score::from_value(score));
+                }
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        let (score, index) = best?;
+        // The best move is searched first in the next iteration
+        root_moves[..=index].rotate_right(1);
+        Some((score, principal_variation))
+    }
+
+    fn minimax(&mut self, chess_board: &ChessBoard, mut chess_moves: Vec<ChessMove>, mut minimax_info: MinimaxInfo, pv: &mut Vec<ChessMove>) -> i32 {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(minimax_info.depth);
+        self.check_limits();
+        if self.stopped() {
+            return 0;
+        }
+        let ply = minimax_info.depth as i32;
+        if chess_moves.is_empty() && ChessBoard::get_party_state(chess_board) == PartyState::CheckMate {
+            return -(MATE - ply);
+        }
+        if chess_board.is_draw() {
+            return self.draw_score(chess_board);
+        }
+        if minimax_info.depth >= MAX_PLY {
+            return self.evaluate(chess_board);
+        }
+        if minimax_info.depth >= minimax_info.max_depth {
+            return self.quiescence(chess_board, chess_moves, minimax_info.alpha, minimax_info.beta, minimax_info.depth);
+        }
+
+        // Mate distance pruning: no line from here can beat a mate already found closer to the root
+        minimax_info.alpha = minimax_info.alpha.max(-(MATE - ply));
+        minimax_info.beta = minimax_info.beta.min(MATE - ply - 1);
+        if minimax_info.alpha >= minimax_info.beta {
+            return minimax_info.alpha;
+        }
+
+        let hash = ChessBoard::get_hash(chess_board);
+        let remaining_depth = minimax_info.max_depth - minimax_info.depth;
+        let original_alpha = minimax_info.alpha;
+        let mut tt_move = None;
+        if let Some(entry) = self.tt.probe(hash) {
+            let tt_score = score_from_tt(entry.score, ply);
+            if entry.depth >= remaining_depth {
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => tt_score >= minimax_info.beta,
+                    Bound::Upper => tt_score <= minimax_info.alpha,
+                };
+                if cutoff {
+                    return tt_score;
+                }
+            }
+            tt_move = entry.best_move;
+        }
+
+        let in_check = ChessBoard::get_party_state(chess_board) == PartyState::Check;
+        let static_eval = if in_check { -INFINITY } else { self.evaluate(chess_board) };
+        let params = self.config.params.clone();
+
+        // Razoring: far below alpha near the leaves, only a capture sequence could save the
+        // position, so ask quiescence and give up if it does not.
+        if self.config.razoring
+            && !in_check
+            && remaining_depth < params.razoring_margins.len()
+            && minimax_info.alpha.abs() < MATE_BOUND
+            && static_eval + params.razoring_margins[remaining_depth] <= minimax_info.alpha
+        {
+            let alpha = minimax_info.alpha;
+            let score = self.quiescence(chess_board, chess_moves.clone(), alpha, alpha + 1, minimax_info.depth);
+            if self.stopped() {
+                return 0;
+            }
+            if score <= alpha {
+                self.stats.razoring_cutoffs += 1;
+                return score;
+            }
+        }
+
+        // Futility: at frontier nodes a quiet move cannot win back more than the margin
+        let futile = self.config.futility_pruning
+            && !in_check
+            && remaining_depth < params.futility_margins.len()
+            && minimax_info.alpha.abs() < MATE_BOUND
+            && static_eval + params.futility_margins[remaining_depth] <= minimax_info.alpha;
+
+        // Null move: if passing still fails high, a real move will too. Skipped in check and
+        // when only pawns are left, where zugzwang makes passing better than any move.
+        if self.config.null_move
+            && minimax_info.allow_null
+            && !in_check
+            && remaining_depth > NULL_MOVE_REDUCTION
+            && minimax_info.beta.abs() < MATE_BOUND
+            && has_non_pawn_material(chess_board)
+            && static_eval >= minimax_info.beta
+        {
+            self.stats.null_move_tries += 1;
+            let mut chess_board_clone = chess_board.clone();
+            let new_all_moves = ChessBoard::make_null_move(&mut chess_board_clone);
+            let null_info = MinimaxInfo {
+                depth : minimax_info.depth + 1,
+                max_depth : minimax_info.max_depth - NULL_MOVE_REDUCTION,
+                alpha : -minimax_info.beta,
+                beta : -minimax_info.beta + 1,
+                allow_null : false,
+                last_capture : None,
+                ..minimax_info
+            };
+            let score = -self.minimax(&chess_board_clone, new_all_moves, null_info, &mut vec![]);
+            if self.stopped() {
+                return 0;
+            }
+            if score >= minimax_info.beta {
+                self.stats.null_move_cutoffs += 1;
+                // A mate found after passing is not a proof of anything
+                return if score >= MATE_BOUND { minimax_info.beta } else { score };
+            }
+        }
+
+        self.order_moves(chess_board, &mut chess_moves, tt_move.as_ref(), minimax_info.depth);
+
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        for (index, chess_move) in chess_moves.iter().enumerate() {
+            let is_quiet = !is_capture(chess_board, chess_move);
             let mut chess_board_clone = chess_board.clone();
-            let new_all_moves = ChessBoard::update_board(&mut chess_board_clone,chess_move.clone());
-            
-            let (score , _ ) = minimax(chess_board_clone,new_all_moves,minimax_info);
+            let new_all_moves = ChessBoard::update_board(&mut chess_board_clone, chess_move.clone());
+            let gives_check = matches!(ChessBoard::get_party_state(&chess_board_clone), PartyState::Check | PartyState::CheckMate);
+            if futile && index > 0 && is_quiet && !gives_check {
+                self.stats.futility_prunes += 1;
+                continue;
+            }
+            let extension = self.extension(chess_board, chess_move, chess_moves.len(), gives_check, &minimax_info);
+            let child_info = MinimaxInfo {
+                depth : minimax_info.depth + 1,
+                max_depth : minimax_info.max_depth + extension,
+                alpha : -minimax_info.beta,
+                beta : -minimax_info.alpha,
+                allow_null : true,
+                extensions : minimax_info.extensions + extension,
+                last_capture : capture_square(chess_board, chess_move),
+            };
+            let mut child_pv = vec![];
+
+            // Late move reductions: quiet moves ordered late rarely turn out best, look at them
+            // with a reduced null window first and search them again only if they beat alpha.
+            let mut needs_full_search = true;
+            let mut score = 0;
+            if self.config.late_move_reductions
+                && index >= LMR_FULL_DEPTH_MOVES
+                && remaining_depth >= LMR_MIN_DEPTH
+                && is_quiet
+                && !in_check
+                && extension == 0
+            {
+                let reduction = if index >= 2 * LMR_FULL_DEPTH_MOVES && remaining_depth >= 2 * LMR_MIN_DEPTH { 2 } else { 1 };
+                self.stats.lmr_reductions += 1;
+                let reduced_info = MinimaxInfo {
+                    max_depth : minimax_info.max_depth - reduction,
+                    alpha : -minimax_info.alpha - 1,
+                    beta : -minimax_info.alpha,
+                    ..child_info
+                };
+                score = -self.minimax(&chess_board_clone, new_all_moves.clone(), reduced_info, &mut child_pv);
+                needs_full_search = score > minimax_info.alpha;
+                if needs_full_search {
+                    self.stats.lmr_researches += 1;
+                    child_pv.clear();
+                }
+            }
+            if needs_full_search {
+                score = -self.minimax(&chess_board_clone, new_all_moves, child_info, &mut child_pv);
+            }
+            if self.stopped() {
+                return 0;
+            }
             if score > best_score {
                 best_score = score;
                 best_move = Some(chess_move.clone());
             }
-            if minimax_info.alpha >= best_score {
-                minimax_info.alpha = best_score
+            if score > minimax_info.alpha {
+                minimax_info.alpha = score;
+                pv.clear();
+                pv.push(chess_move.clone());
+                pv.extend(child_pv);
             }
             if minimax_info.alpha >= minimax_info.beta {
+                if is_quiet {
+                    self.update_quiet_stats(chess_board, chess_move, minimax_info.depth, remaining_depth);
+                }
                 break;
             }
         }
+
+        let bound = if best_score >= minimax_info.beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(hash, best_move.as_ref(), score_to_tt(best_score, ply), remaining_depth, bound);
+        best_score
+    }
+
+    // Checks, forced replies and (optionally) recaptures are searched one ply deeper, so that
+    // forcing sequences are not cut at the horizon. At most one ply per move, and no more than
+    // `max_extensions` plies along a line.
+    fn extension(&mut self, chess_board: &ChessBoard, chess_move: &ChessMove, move_count: usize, gives_check: bool, minimax_info: &MinimaxInfo) -> usize {
+        if minimax_info.extensions >= self.config.params.max_extensions {
+            return 0;
+        }
+        let is_recapture = minimax_info.last_capture.is_some()
+            && capture_square(chess_board, chess_move) == minimax_info.last_capture;
+        let extend = (self.config.check_extensions && gives_check)
+            || (self.config.single_reply_extensions && move_count == 1)
+            || (self.config.recapture_extensions && is_recapture);
+        if extend {
+            self.stats.extensions += 1;
+            1
+        } else {
+            0
+        }
     }
-    else {
-        best_score = i32::MAX;
+
+    // Only captures are searched (all moves when in check), so that the static evaluation is
+    // never taken in the middle of an exchange.
+    fn quiescence(&mut self, chess_board: &ChessBoard, mut chess_moves: Vec<ChessMove>, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.nodes += 1;
+        self.stats.quiescence_nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        self.check_limits();
+        if self.stopped() {
+            return 0;
+        }
+        let party_state = ChessBoard::get_party_state(chess_board);
+        if chess_moves.is_empty() && party_state == PartyState::CheckMate {
+            return -(MATE - ply as i32);
+        }
+        if chess_board.is_draw() {
+            return self.draw_score(chess_board);
+        }
+        if ply >= MAX_PLY {
+            return self.evaluate(chess_board);
+        }
+
+        let in_check = party_state == PartyState::Check;
+        let stand_pat = if in_check { -INFINITY } else { self.evaluate(chess_board) };
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        if !in_check {
+            chess_moves.retain(|chess_move| is_capture(chess_board, chess_move));
+        }
+        self.order_moves(chess_board, &mut chess_moves, None, ply);
+
+        let mut best_score = stand_pat;
         for chess_move in chess_moves.iter() {
+            if self.config.delta_pruning && !in_check {
+                let gain = captured_piece(chess_board, chess_move).map_or(0, piece_value);
+                if stand_pat + gain + self.config.params.delta_margin < alpha {
+                    self.stats.delta_prunes += 1;
+                    continue;
+                }
+            }
             let mut chess_board_clone = chess_board.clone();
-            let new_all_moves = ChessBoard::update_board(&mut chess_board_clone,chess_move.clone());
-            let (score , _ ) =  minimax(chess_board_clone,new_all_moves,minimax_info);
-            if score < best_score {
+            let new_all_moves = ChessBoard::update_board(&mut chess_board_clone, chess_move.clone());
+            let score = -self.quiescence(&chess_board_clone, new_all_moves, -beta, -alpha, ply + 1);
+            if self.stopped() {
+                return 0;
+            }
+            if score > best_score {
                 best_score = score;
-                best_move = Some(chess_move.clone());
             }
-            if minimax_info.beta > best_score {
-                minimax_info.beta = best_score;
+            if score > alpha {
+                alpha = score;
             }
-            if minimax_info.alpha >= minimax_info.beta {
+            if alpha >= beta {
                 break;
             }
         }
+        best_score
+    }
+
+    // A draw is worth -contempt to the engine: with a positive contempt it plays on rather than
+    // accept a draw against a weaker opponent.
+    fn draw_score(&self, chess_board: &ChessBoard) -> i32 {
+        if ChessBoard::get_turn(chess_board) == self.root_turn {
+            -self.config.contempt
+        } else {
+            self.config.contempt
+        }
+    }
+
+    // Evaluation plus the skill level noise. The offset depends only on the position, so the
+    // transposition table never sees two scores for the same position.
+    fn evaluate(&self, chess_board: &ChessBoard) -> i32 {
+        let score = eval_function(chess_board, self.config.evaluator.as_ref());
+        let noise = self.config.params.eval_noise;
+        if noise <= 0 {
+            return score;
+        }
+        let random = zobrist::splitmix64(chess_board.get_hash() ^ self.config.params.noise_seed).1;
+        score + (random % (2 * noise as u64 + 1)) as i32 - noise
+    }
+
+    // TT move first, then captures by most valuable victim / least valuable attacker, killers
+    // and finally quiet moves by history.
+    fn order_moves(&self, chess_board: &ChessBoard, chess_moves: &mut [ChessMove], tt_move: Option<&ChessMove>, ply: usize) {
+        let color = side(ChessBoard::get_turn(chess_board));
+        chess_moves.sort_by_cached_key(|chess_move| {
+            let score = if Some(chess_move) == tt_move {
+                i32::MAX
+            } else if let Some(victim) = captured_piece(chess_board, chess_move) {
+                let attacker = moving_piece(chess_board, chess_move);
+                1_000_000 + 10 * piece_value(victim) - piece_value(attacker)
+            } else if self.killers[ply][0].as_ref() == Some(chess_move) {
+                900_000
+            } else if self.killers[ply][1].as_ref() == Some(chess_move) {
+                800_000
+            } else {
+                let (from, to) = move_squares(chess_move);
+                self.history[color][from][to]
+            };
+            std::cmp::Reverse(score)
+        });
+    }
+
+    fn update_quiet_stats(&mut self, chess_board: &ChessBoard, chess_move: &ChessMove, ply: usize, remaining_depth: usize) {
+        if self.killers[ply][0].as_ref() != Some(chess_move) {
+            self.killers[ply][1] = self.killers[ply][0].take();
+            self.killers[ply][0] = Some(chess_move.clone());
+        }
+        let color = side(ChessBoard::get_turn(chess_board));
+        let (from, to) = move_squares(chess_move);
+        let entry = &mut self.history[color][from][to];
+        *entry = (*entry + (remaining_depth * remaining_depth) as i32).min(700_000);
+    }
+}
+
+pub(crate) fn nodes_per_second(nodes: u64, elapsed: Duration) -> u64 {
+    (nodes as u128 * 1000 / elapsed.as_millis().max(1)) as u64
+}
+
+fn move_squares(chess_move: &ChessMove) -> (usize, usize) {
+    match chess_move {
+        ChessMove::OrdinaryMove { from, to } => (from.row * 8 + from.col, to.row * 8 + to.col),
+        ChessMove::Castle { king, rook } => (king.row * 8 + king.col, rook.row * 8 + rook.col),
     }
-    (best_score,best_move)
 }
 
+fn moving_piece(chess_board: &ChessBoard, chess_move: &ChessMove) -> PieceType {
+    match chess_move {
+        ChessMove::OrdinaryMove { from, .. } => ChessBoard::get_piece_from_pos(chess_board, from).map_or(PieceType::None, |piece| piece.piece_type),
+        ChessMove::Castle { .. } => PieceType::King,
+    }
+}
 
-fn eval_function(chess_board: ChessBoard, minimax_info: MinimaxInfo) -> i32 {
+fn captured_piece(chess_board: &ChessBoard, chess_move: &ChessMove) -> Option<PieceType> {
+    match chess_move {
+        ChessMove::OrdinaryMove { to, .. } => ChessBoard::get_piece_from_pos(chess_board, to).map(|piece| piece.piece_type),
+        ChessMove::Castle { .. } => None,
+    }
+}
+
+fn capture_square(chess_board: &ChessBoard, chess_move: &ChessMove) -> Option<Position> {
+    match chess_move {
+        ChessMove::OrdinaryMove { to, .. } if is_capture(chess_board, chess_move) => Some(*to),
+        _ => None,
+    }
+}
+
+fn is_capture(chess_board: &ChessBoard, chess_move: &ChessMove) -> bool {
+    captured_piece(chess_board, chess_move).is_some()
+}
+
+fn has_non_pawn_material(chess_board: &ChessBoard) -> bool {
+    ChessBoard::get_friendly_pieces(chess_board).iter()
+        .any(|piece| piece.piece_type != PieceType::Pawn && piece.piece_type != PieceType::King)
+}
+
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 300,
+        PieceType::Bishop => 300,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        _ => 0,
+    }
+}
+
+// Scores every legal move with its own full search, best first. This is MultiPV with as many
+// lines as there are moves, run on a private single-threaded search.
+pub fn analyze_all_moves(chess_board: &ChessBoard, limits: SearchLimits) -> Vec<(ChessMove, Score)> {
+    analyze_all_moves_with_config(chess_board, &SearchConfig::default(), limits)
+}
+
+// Same with the caller's evaluator and search switches, so that the CLI options also apply to
+// the analysis. Only the thread count and the number of lines are overridden.
+pub fn analyze_all_moves_with_config(chess_board: &ChessBoard, config: &SearchConfig, limits: SearchLimits) -> Vec<(ChessMove, Score)> {
+    let move_count = ChessBoard::get_party_moves(chess_board).len();
+    if move_count == 0 {
+        return vec![];
+    }
+    let config = SearchConfig { threads: 1, multi_pv: move_count, ..config.clone() };
+    let tt = Arc::new(TranspositionTable::new(config.hash_size_mb));
+    let clock = SearchClock::new(&limits, ChessBoard::get_turn(chess_board));
+    let mut search_thread = SearchThread::new(0, tt, config, StopSignal::new(), PonderSignal::new(false), clock, Arc::new(AtomicU64::new(0)));
+    let report = search_thread.iterative_deepening(chess_board, &limits);
+    // Without a completed iteration the lines are placeholders, not scores
+    if report.depth == 0 {
+        return vec![];
+    }
+    report.lines.into_iter()
+        .filter_map(|(score, principal_variation)| {
+            principal_variation.into_iter().next().map(|chess_move| (chess_move, Score::from_value(score)))
+        })
+        .collect()
+}
+
+// Mate scores are stored relative to the node rather than to the root, so that an entry stays
+// valid when the position is reached again at another ply.
+fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply
+    } else if score <= -MATE_BOUND {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply
+    } else if score <= -MATE_BOUND {
+        score + ply
+    } else {
+        score
+    }
+}
+
+// Score du point de vue du camp qui a le trait
+pub(crate) fn eval_function(chess_board: &ChessBoard, evaluator: &dyn Evaluator) -> i32 {
     // Vérifier l'état de la partie
-    let party_state =  ChessBoard::get_party_state(&chess_board);
+    let party_state =  ChessBoard::get_party_state(chess_board);
     match party_state {
-        PartyState::CheckMate => {
-            println!("found check mate");
-            if minimax_info.color == ChessBoard::get_turn(&chess_board) {
-                i32::MIN
-            } else {
+        PartyState::CheckMate => -MATE,
+        PartyState::Pat => 0,
+        _ => evaluator.evaluate(chess_board),
+    }
+}
 
-                i32::MAX
-            }
-        },
-        PartyState::Pat => {
-            10
-
-        },
-        _ => {
-            let mut friend_points : i32 = 0;
-            let mut ennemy_points : i32 = 0;
-            for piece in ChessBoard::get_ennemy_pieces(&chess_board) {
-                match piece.piece_type {
-                    PieceType::Rook => ennemy_points+=5,
-                    PieceType::Bishop => ennemy_points+=3,
-                    PieceType::Knight => ennemy_points+=3,
-                    PieceType::Queen => ennemy_points+=9,
-                    PieceType::Pawn =>  ennemy_points+= 1,
-                    _ => (),
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_pool::SearchPool;
+
+    fn search(fen: &str, config: SearchConfig, limits: SearchLimits) -> SearchResult {
+        let mut search_pool = SearchPool::new(SearchConfig { threads: 1, ..config });
+        search_pool.start_minimax(&ChessBoard::from_fen(fen).unwrap(), limits)
+    }
+
+    #[test]
+    fn scores_round_trip_through_values() {
+        for score in [Score::Cp(0), Score::Cp(137), Score::Cp(-MATE_BOUND + 1), Score::Mate(1), Score::Mate(7), Score::Mate(-1), Score::Mate(-7)] {
+            assert_eq!(Score::from_value(score.to_value()), score);
+        }
+        // Mating sooner is better, being mated later is better
+        assert!(Score::Mate(1).to_value() > Score::Mate(2).to_value());
+        assert!(Score::Mate(-2).to_value() > Score::Mate(-1).to_value());
+        assert!(Score::Mate(-1).to_value() < Score::Cp(-MATE_BOUND + 1).to_value());
+    }
+
+    #[test]
+    fn mate_scores_are_stored_relative_to_the_node() {
+        for value in [0, 250, -250, MATE - 5, -(MATE - 6)] {
+            for ply in [0, 3, 10] {
+                assert_eq!(score_from_tt(score_to_tt(value, ply), ply), value);
             }
-            for piece in ChessBoard::get_friendly_pieces(&chess_board) {
-                match piece.piece_type {
-                    PieceType::Rook => friend_points+=5,
-                    PieceType::Bishop => friend_points+=3,
-                    PieceType::Knight => friend_points+=3,
-                    PieceType::Queen => friend_points+=9,
-                    PieceType::Pawn =>  friend_points+= 1,
-                    _ => (),
-                }
+        }
+        // A mate 2 plies below a node reached at ply 3, probed again at ply 1
+        assert_eq!(score_to_tt(MATE - 5, 3), MATE - 2);
+        assert_eq!(score_from_tt(MATE - 2, 1), MATE - 3);
+        assert_eq!(score_from_tt(score_to_tt(-(MATE - 6), 4), 2), -(MATE - 4));
+    }
+
+    #[test]
+    fn search_finds_a_mate_in_two() {
+        let fen = "4kb1r/p2n1ppp/4q3/4p1B1/4P3/1Q6/PPP2PPP/2KR4 w - - 1 17";
+        let search_result = search(fen, SearchConfig::default(), SearchLimits::depth(4));
+        assert_eq!(search_result.score, Score::Mate(2));
+        // Qb8+ Nxb8 Rd8#
+        let queen_sacrifice = ChessMove::OrdinaryMove { from: Position { row: 2, col: 1 }, to: Position { row: 7, col: 1 } };
+        assert_eq!(search_result.best_move, Some(queen_sacrifice));
+    }
+
+    #[test]
+    fn multi_pv_lines_are_distinct_and_sorted() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let search_result = search(fen, SearchConfig { multi_pv: 4, ..SearchConfig::default() }, SearchLimits::depth(3));
+        assert_eq!(search_result.lines.len(), 4);
+        let first_moves: Vec<&ChessMove> = search_result.lines.iter().map(|line| &line.principal_variation[0]).collect();
+        for (index, chess_move) in first_moves.iter().enumerate() {
+            assert!(!first_moves[..index].contains(chess_move), "{:?} twice", chess_move);
+        }
+        assert!(search_result.lines.windows(2).all(|pair| pair[0].score.to_value() >= pair[1].score.to_value()));
+        assert_eq!(search_result.best_move.as_ref(), Some(first_moves[0]));
+        assert_eq!(search_result.score, search_result.lines[0].score);
+    }
+
+    #[test]
+    fn extensions_find_a_mate_beyond_the_depth() {
+        // Philidor's smothered mate, Nh6+ Kh8 Qg8+ Rxg8 Nf7#, is five plies long: only the checks
+        // and forced replies being extended bring it within a four-ply search
+        let fen = "5rk1/5Npp/8/8/2Q5/8/6PP/6K1 w - - 0 1";
+        let extended = search(fen, SearchConfig::default(), SearchLimits::depth(4));
+        assert_eq!(extended.score, Score::Mate(3));
+        assert!(extended.stats.extensions > 0);
+        let config = SearchConfig { check_extensions: false, single_reply_extensions: false, ..SearchConfig::default() };
+        assert!(matches!(search(fen, config, SearchLimits::depth(4)).score, Score::Cp(_)));
+    }
+
+    #[test]
+    fn draws_score_minus_contempt_for_the_root_side() {
+        // Every move of the bishop side leaves a bare king and bishop, a dead position
+        for fen in ["8/8/8/4k3/8/8/8/2B1K3 w - - 0 1", "8/8/8/4k3/8/8/8/2B1K3 b - - 0 1"] {
+            for contempt in [25, -25] {
+                let search_result = search(fen, SearchConfig { contempt, ..SearchConfig::default() }, SearchLimits::depth(3));
+                assert_eq!(search_result.score, Score::Cp(-contempt), "{} with contempt {}", fen, contempt);
             }
-            return friend_points - ennemy_points
         }
     }
+
+    #[test]
+    fn analysis_scores_every_move_even_with_a_tiny_budget() {
+        let fen = "r1bq1rk1/ppp2ppp/2np1n2/2b1p3/2B1P3/2NP1N2/PPP2PPP/R1BQ1RK1 w - - 0 7";
+        let chess_board = ChessBoard::from_fen(fen).unwrap();
+        let legal_moves = ChessBoard::get_party_moves(&chess_board);
+        let analysis = analyze_all_moves(&chess_board, SearchLimits::movetime(Duration::from_millis(1)));
+        assert_eq!(analysis.len(), legal_moves.len());
+        assert!(legal_moves.iter().all(|chess_move| analysis.iter().any(|(analysed, _)| analysed == chess_move)));
+        assert!(analysis.windows(2).all(|pair| pair[0].1.to_value() >= pair[1].1.to_value()));
+    }
 }
-    
\ No newline at end of file
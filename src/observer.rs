@@ -0,0 +1,36 @@
+use crate::chess_board::ChessMove;
+use crate::minimax::Score;
+use std::time::Duration;
+
+// State of the search when an iteration or a line completes, the content of a UCI `info` line
+#[derive(Clone,Debug)]
+pub struct SearchInfo {
+    pub depth : usize,
+    pub seldepth : usize,
+    // 1 for the main line, k for the k-th best MultiPV line
+    pub multi_pv : usize,
+    pub score : Score,
+    pub nodes : u64,
+    pub nps : u64,
+    pub elapsed : Duration,
+    pub principal_variation : Vec<ChessMove>,
+}
+
+// Receives the progress of a running search. Only the main search thread reports, but it does
+// so from its own thread, hence Send + Sync. Every event has an empty default so front-ends
+// implement only the ones they show.
+pub trait SearchObserver: Send + Sync {
+    // A line of the iteration at `info.depth` is complete
+    fn on_depth(&self, _info: &SearchInfo) {}
+
+    // The best root move changed during the iteration at `depth`
+    fn on_best_move(&self, _depth: usize, _chess_move: &ChessMove, _score: Score) {}
+
+    // The root move `chess_move`, number `move_number` from 1, is being searched
+    fn on_current_move(&self, _depth: usize, _chess_move: &ChessMove, _move_number: usize) {}
+
+    // Sent about every `PROGRESS_INTERVAL` while the search runs
+    fn on_progress(&self, _nodes: u64, _nps: u64, _elapsed: Duration) {}
+}
+
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
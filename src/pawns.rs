@@ -0,0 +1,316 @@
+use crate::chess_board::{ChessBoard, Color, PieceType, Position};
+use crate::zobrist;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const DEFAULT_PAWN_HASH_MB: usize = 1;
+
+// Middlegame and endgame weights in centipawns
+const DOUBLED: (i32, i32) = (-10, -20);
+const ISOLATED: (i32, i32) = (-10, -15);
+const BACKWARD: (i32, i32) = (-8, -10);
+// By rank from the pawn's own side, for a pawn defended by or side by side with another pawn
+const CONNECTED: [i32; 8] = [0, 5, 7, 10, 15, 25, 40, 0];
+const PASSED_MG: [i32; 8] = [0, 5, 10, 15, 25, 45, 70, 0];
+const PASSED_EG: [i32; 8] = [0, 10, 15, 25, 45, 75, 120, 0];
+// Part of the passed pawn bonus kept when a piece stands on the square in front of it
+const BLOCKED_PASSED_PERCENT: i32 = 50;
+
+pub(crate) const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+// Structure of the pawns alone, White minus Black. Passed pawns are kept apart because their
+// bonus also depends on the pieces blocking them, which the pawn key does not see.
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct PawnEval {
+    pub mg : i32,
+    pub eg : i32,
+    // Squares (row * 8 + col) of the passed pawns of both colors
+    pub passed : u64,
+}
+
+// Same lockless layout as the transposition table: the key is stored xor-ed with the data.
+struct Slot {
+    check : AtomicU64,
+    score : AtomicU64,
+    passed : AtomicU64,
+}
+
+// Pawn structures change much less often than positions, so their evaluation is cached by a
+// key over the pawns only.
+pub struct PawnTable {
+    slots : Vec<Slot>,
+}
+
+impl PawnTable {
+    pub fn new(size_mb: usize) -> Self {
+        let len = ((size_mb.max(1) * 1024 * 1024) / std::mem::size_of::<Slot>()).max(1);
+        let slots = (0..len)
+            .map(|_| Slot { check: AtomicU64::new(0), score: AtomicU64::new(0), passed: AtomicU64::new(0) })
+            .collect();
+        PawnTable { slots }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<PawnEval> {
+        let slot = &self.slots[self.index(key)];
+        let score = slot.score.load(Ordering::Relaxed);
+        let passed = slot.passed.load(Ordering::Relaxed);
+        let check = slot.check.load(Ordering::Relaxed);
+        if check == 0 || check ^ score ^ passed != key {
+            return None;
+        }
+        Some(PawnEval { mg: score as u32 as i32, eg: (score >> 32) as u32 as i32, passed })
+    }
+
+    pub fn store(&self, key: u64, pawn_eval: &PawnEval) {
+        let slot = &self.slots[self.index(key)];
+        let score = pawn_eval.mg as u32 as u64 | (pawn_eval.eg as u32 as u64) << 32;
+        slot.check.store(key ^ score ^ pawn_eval.passed, Ordering::Relaxed);
+        slot.score.store(score, Ordering::Relaxed);
+        slot.passed.store(pawn_eval.passed, Ordering::Relaxed);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        ((key as u128 * self.slots.len() as u128) >> 64) as usize
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        PawnTable::new(DEFAULT_PAWN_HASH_MB)
+    }
+}
+
+impl fmt::Debug for PawnTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PawnTable {{ {} slots }}", self.slots.len())
+    }
+}
+
+// Zobrist key of the pawns only. It is never 0 with pawns on the board, 0 is the empty slot.
+pub fn pawn_key(chess_board: &ChessBoard) -> u64 {
+    let (white, black) = pawn_bitboards(chess_board);
+    squares(white).map(|square| zobrist::piece_key(PieceType::Pawn, Color::White, position(square)))
+        .chain(squares(black).map(|square| zobrist::piece_key(PieceType::Pawn, Color::Black, position(square))))
+        .fold(0, |key, piece_key| key ^ piece_key)
+}
+
+// Cached structure plus the passed pawn bonuses, White minus Black
+pub fn pawn_score(chess_board: &ChessBoard, pawn_table: &PawnTable) -> (i32, i32) {
+    let key = pawn_key(chess_board);
+    let pawn_eval = pawn_table.probe(key).unwrap_or_else(|| {
+        let pawn_eval = evaluate_pawns(chess_board);
+        pawn_table.store(key, &pawn_eval);
+        pawn_eval
+    });
+    let (passed_mg, passed_eg) = passed_pawn_bonus(chess_board, pawn_eval.passed);
+    (pawn_eval.mg + passed_mg, pawn_eval.eg + passed_eg)
+}
+
+// Doubled, isolated, backward and connected pawns, and the list of passed pawns
+pub fn evaluate_pawns(chess_board: &ChessBoard) -> PawnEval {
+    let (white, black) = pawn_bitboards(chess_board);
+    let (white_mg, white_eg, white_passed) = side_structure(white, black, Color::White);
+    let (black_mg, black_eg, black_passed) = side_structure(black, white, Color::Black);
+    PawnEval { mg: white_mg - black_mg, eg: white_eg - black_eg, passed: white_passed | black_passed }
+}
+
+// Bonus of the passed pawns by rank, reduced when the square in front of the pawn is occupied
+pub fn passed_pawn_bonus(chess_board: &ChessBoard, passed: u64) -> (i32, i32) {
+    let (mut mg, mut eg) = (0, 0);
+    for square in squares(passed) {
+        let Some(pawn) = chess_board.get_piece_from_pos(&position(square)) else {
+            continue;
+        };
+        let rank = relative_rank(square / 8, pawn.color);
+        let (mut pawn_mg, mut pawn_eg) = (PASSED_MG[rank], PASSED_EG[rank]);
+        let stop_row = if pawn.color == Color::White { square / 8 + 1 } else { (square / 8).wrapping_sub(1) };
+        if stop_row < 8 && chess_board.get_piece_from_pos(&Position { row: stop_row, col: square % 8 }).is_some() {
+            pawn_mg = pawn_mg * BLOCKED_PASSED_PERCENT / 100;
+            pawn_eg = pawn_eg * BLOCKED_PASSED_PERCENT / 100;
+        }
+        let sign = if pawn.color == Color::White { 1 } else { -1 };
+        mg += sign * pawn_mg;
+        eg += sign * pawn_eg;
+    }
+    (mg, eg)
+}
+
+// Structure and passed pawn bonus of one side alone, uncached, for the evaluation trace
+pub fn side_pawn_terms(chess_board: &ChessBoard, color: Color) -> ((i32, i32), (i32, i32)) {
+    let (white, black) = pawn_bitboards(chess_board);
+    let (own, enemy) = if color == Color::White { (white, black) } else { (black, white) };
+    let (mg, eg, passed) = side_structure(own, enemy, color);
+    let (passed_mg, passed_eg) = passed_pawn_bonus(chess_board, passed);
+    let sign = if color == Color::White { 1 } else { -1 };
+    ((mg, eg), (sign * passed_mg, sign * passed_eg))
+}
+
+fn side_structure(own: u64, enemy: u64, color: Color) -> (i32, i32, u64) {
+    let (mut mg, mut eg) = (0, 0);
+    let mut passed = 0;
+    for col in 0..8 {
+        let count = (own & (FILE_A << col)).count_ones() as i32;
+        if count > 1 {
+            mg += DOUBLED.0 * (count - 1);
+            eg += DOUBLED.1 * (count - 1);
+        }
+    }
+    for square in squares(own) {
+        let (row, col) = (square / 8, square % 8);
+        let adjacent = adjacent_files(col);
+        let rank = relative_rank(row, color);
+        // Rows strictly in front of the pawn, and the row just behind it, from its own side
+        let front = rows_in_front(row, color);
+        let behind_row = if color == Color::White { row.wrapping_sub(1) } else { row + 1 };
+        let phalanx = own & adjacent & row_mask(row);
+        let support = if behind_row < 8 { own & adjacent & row_mask(behind_row) } else { 0 };
+        if enemy & (adjacent | FILE_A << col) & front == 0 {
+            passed |= 1 << square;
+        }
+        if own & adjacent == 0 {
+            mg += ISOLATED.0;
+            eg += ISOLATED.1;
+        } else if phalanx | support != 0 {
+            mg += CONNECTED[rank];
+            eg += CONNECTED[rank];
+        } else if own & adjacent & !front == 0 && stop_square_attacked(row, col, enemy, color) {
+            // Every neighbour has gone ahead, none can defend its advance
+            mg += BACKWARD.0;
+            eg += BACKWARD.1;
+        }
+    }
+    (mg, eg, passed)
+}
+
+// An enemy pawn guards the square in front of the pawn
+fn stop_square_attacked(row: usize, col: usize, enemy: u64, color: Color) -> bool {
+    let attacker_row = if color == Color::White { row + 2 } else { row.wrapping_sub(2) };
+    attacker_row < 8 && enemy & adjacent_files(col) & row_mask(attacker_row) != 0
+}
+
+fn pawn_bitboards(chess_board: &ChessBoard) -> (u64, u64) {
+    let (mut white, mut black) = (0u64, 0u64);
+    for piece in chess_board.get_friendly_pieces().iter().chain(chess_board.get_ennemy_pieces().iter()) {
+        if piece.piece_type != PieceType::Pawn {
+            continue;
+        }
+        let position = piece.get_position();
+        let bit = 1u64 << (position.row * 8 + position.col);
+        if piece.color == Color::White { white |= bit } else { black |= bit }
+    }
+    (white, black)
+}
+
+pub(crate) fn squares(mut bitboard: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            return None;
+        }
+        let square = bitboard.trailing_zeros() as usize;
+        bitboard &= bitboard - 1;
+        Some(square)
+    })
+}
+
+pub(crate) fn position(square: usize) -> Position {
+    Position { row: square / 8, col: square % 8 }
+}
+
+fn relative_rank(row: usize, color: Color) -> usize {
+    if color == Color::White { row } else { 7 - row }
+}
+
+pub(crate) fn row_mask(row: usize) -> u64 {
+    0xFF << (row * 8)
+}
+
+pub(crate) fn adjacent_files(col: usize) -> u64 {
+    let left = if col > 0 { FILE_A << (col - 1) } else { 0 };
+    let right = if col < 7 { FILE_A << (col + 1) } else { 0 };
+    left | right
+}
+
+pub(crate) fn rows_in_front(row: usize, color: Color) -> u64 {
+    if color == Color::White {
+        if row >= 7 { 0 } else { !0u64 << ((row + 1) * 8) }
+    } else {
+        (1u64 << (row * 8)) - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::BENCH_POSITIONS;
+
+    fn bits(names: &[&str]) -> u64 {
+        names.iter().fold(0, |mask, name| {
+            let name = name.as_bytes();
+            mask | 1 << ((name[1] - b'1') as usize * 8 + (name[0] - b'a') as usize)
+        })
+    }
+
+    #[test]
+    fn doubled_and_isolated_pawns() {
+        // e2 e3 : doublés et isolés, et tous deux passés faute de pion adverse
+        let (mg, eg, passed) = side_structure(bits(&["e2", "e3"]), 0, Color::White);
+        assert_eq!((mg, eg), (DOUBLED.0 + 2 * ISOLATED.0, DOUBLED.1 + 2 * ISOLATED.1));
+        assert_eq!(passed, bits(&["e2", "e3"]));
+        let (mg, eg, _) = side_structure(bits(&["a2", "c2"]), bits(&["h7"]), Color::White);
+        assert_eq!((mg, eg), (2 * ISOLATED.0, 2 * ISOLATED.1));
+    }
+
+    #[test]
+    fn connected_and_backward_pawns() {
+        let (mg, eg, _) = side_structure(bits(&["d4", "e4"]), 0, Color::White);
+        assert_eq!((mg, eg), (2 * CONNECTED[3], 2 * CONNECTED[3]));
+        // d2 a laissé e3 partir devant et c4 tient d3 : d2 est arriéré, e3 est soutenu
+        let (mg, eg, passed) = side_structure(bits(&["d2", "e3"]), bits(&["c4"]), Color::White);
+        assert_eq!((mg, eg), (BACKWARD.0 + CONNECTED[2], BACKWARD.1 + CONNECTED[2]));
+        assert_eq!(passed, bits(&["e3"]));
+        // Same structure seen from Black
+        let (mg, eg, _) = side_structure(bits(&["d7", "e6"]), bits(&["c5"]), Color::Black);
+        assert_eq!((mg, eg), (BACKWARD.0 + CONNECTED[2], BACKWARD.1 + CONNECTED[2]));
+    }
+
+    #[test]
+    fn passed_pawns() {
+        let (_, _, passed) = side_structure(bits(&["a5", "e5"]), bits(&["d7", "h7"]), Color::White);
+        assert_eq!(passed, bits(&["a5"]));
+        let (_, _, passed) = side_structure(bits(&["d7", "h7"]), bits(&["a5", "e5"]), Color::Black);
+        assert_eq!(passed, bits(&["h7"]));
+        // Un pion passé bloqué ne garde que la moitié de son bonus
+        let free = ChessBoard::from_fen("4k3/8/8/P7/8/8/8/4K3 w - - 0 1").unwrap();
+        let blocked = ChessBoard::from_fen("4k3/8/n7/P7/8/8/8/4K3 w - - 0 1").unwrap();
+        let passed = bits(&["a5"]);
+        assert_eq!(passed_pawn_bonus(&free, passed), (PASSED_MG[4], PASSED_EG[4]));
+        assert_eq!(passed_pawn_bonus(&blocked, passed), (PASSED_MG[4] / 2, PASSED_EG[4] / 2));
+    }
+
+    #[test]
+    fn pawn_table_round_trip() {
+        let pawn_table = PawnTable::new(1);
+        let pawn_eval = PawnEval { mg: -37, eg: 12, passed: bits(&["a5", "h2"]) };
+        let key = 0x1234_5678_9ABC_DEF0;
+        assert_eq!(pawn_table.probe(key), None);
+        pawn_table.store(key, &pawn_eval);
+        assert_eq!(pawn_table.probe(key), Some(pawn_eval));
+        // A key falling in the same slot replaces the entry, the old key no longer matches
+        pawn_table.store(key + 1, &PawnEval::default());
+        assert_eq!(pawn_table.probe(key), None);
+        assert_eq!(pawn_table.probe(key + 1), Some(PawnEval::default()));
+    }
+
+    #[test]
+    fn cached_score_matches_the_uncached_one() {
+        let pawn_table = PawnTable::default();
+        for fen in BENCH_POSITIONS.iter() {
+            let chess_board = ChessBoard::from_fen(fen).unwrap();
+            let pawn_eval = evaluate_pawns(&chess_board);
+            let (passed_mg, passed_eg) = passed_pawn_bonus(&chess_board, pawn_eval.passed);
+            let expected = (pawn_eval.mg + passed_mg, pawn_eval.eg + passed_eg);
+            assert_eq!(pawn_score(&chess_board, &pawn_table), expected, "{}", fen);
+            assert_eq!(pawn_score(&chess_board, &pawn_table), expected, "{}", fen);
+        }
+    }
+}
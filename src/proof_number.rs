@@ -0,0 +1,309 @@
+use crate::chess_board::{ChessBoard, ChessMove, PartyState};
+use std::collections::HashMap;
+
+const INFINITE: u32 = u32::MAX;
+// Tree nodes and proven positions kept in memory at once when no limit is given
+pub const DEFAULT_MAX_NODES: usize = 1_000_000;
+pub const DEFAULT_MAX_MOVES: usize = 50;
+
+// King and rook against king, the ending hard-coded in the old temp.rs prototype. The forced
+// mates run from 2 to 16 moves, far past what the alpha-beta search sees.
+pub const KRK_POSITIONS: [&str; 4] = [
+    "7k/8/5K2/8/8/8/8/R7 w - - 0 1",
+    "8/k7/8/2K5/8/8/8/7R w - - 0 1",
+    "8/1k6/8/3K4/8/8/8/7R w - - 0 1",
+    "8/8/2k5/8/3K4/8/8/7R w - - 0 1",
+];
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum ProofResult {
+    // Forced mate for the side to move, with the line of the most stubborn defence. The search
+    // stops at the first proof, so `moves` is the length of that line, an upper bound on the
+    // shortest mate rather than the shortest mate itself.
+    Proven { moves: usize, line: Vec<ChessMove> },
+    // The defender escapes: stalemate, repetition or no mate within the move bound
+    Disproven,
+    // Memory limit (tree nodes and proven positions) reached before the root was solved
+    Unknown,
+}
+
+struct PnNode {
+    chess_move : Option<ChessMove>,
+    hash : u64,
+    parent : Option<usize>,
+    children : Vec<usize>,
+    // Attacker to move: one proven child is enough
+    or_node : bool,
+    proof : u32,
+    disproof : u32,
+    // Plies to mate once proven
+    distance : usize,
+    expanded : bool,
+}
+
+// Proof-number search (Allis): the tree grows toward the most-proving node, the leaf whose
+// solution would change the root the most, so narrow forcing lines are followed far beyond the
+// depth alpha-beta reaches. Solved subtrees are freed, and the tree together with the table of
+// proven positions is kept within `max_nodes` entries.
+pub struct ProofNumberSearch {
+    max_nodes : usize,
+    max_plies : usize,
+    nodes : Vec<PnNode>,
+    free : Vec<usize>,
+    // Proven positions with their distance to mate and the next move of the main line. Proofs
+    // do not depend on the path, so they are shared between transpositions.
+    proven : HashMap<u64, (usize, Option<ChessMove>)>,
+    expansions : u64,
+}
+
+impl ProofNumberSearch {
+    pub fn new(max_nodes: usize, max_moves: usize) -> Self {
+        ProofNumberSearch {
+            max_nodes : max_nodes.max(1),
+            max_plies : max_moves * 2,
+            nodes : vec![],
+            free : vec![],
+            proven : HashMap::new(),
+            expansions : 0,
+        }
+    }
+
+    pub fn expansions(&self) -> u64 {
+        self.expansions
+    }
+
+    pub fn prove(&mut self, chess_board: &ChessBoard) -> ProofResult {
+        self.nodes.clear();
+        self.free.clear();
+        self.proven.clear();
+        self.expansions = 0;
+        let moves = ChessBoard::get_party_moves(chess_board);
+        if moves.is_empty() {
+            return ProofResult::Disproven;
+        }
+        self.allocate(PnNode {
+            chess_move : None,
+            hash : chess_board.get_hash(),
+            parent : None,
+            children : vec![],
+            or_node : true,
+            proof : 1,
+            disproof : moves.len() as u32,
+            distance : 0,
+            expanded : false,
+        });
+        while self.nodes[0].proof != 0 && self.nodes[0].disproof != 0 {
+            if self.nodes.len() - self.free.len() + self.proven.len() >= self.max_nodes {
+                return ProofResult::Unknown;
+            }
+            let (node, board, path, ply) = self.select_most_proving(chess_board);
+            self.expand(node, &board, &path, ply);
+            self.update_ancestors(node);
+        }
+        if self.nodes[0].proof != 0 {
+            return ProofResult::Disproven;
+        }
+        let (distance, _) = self.proven[&chess_board.get_hash()];
+        ProofResult::Proven { moves: distance.div_ceil(2), line: self.main_line(chess_board) }
+    }
+
+    fn allocate(&mut self, node: PnNode) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            },
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            },
+        }
+    }
+
+    fn free_subtree(&mut self, index: usize) {
+        let children = std::mem::take(&mut self.nodes[index].children);
+        for child in children {
+            self.free_subtree(child);
+        }
+        self.free.push(index);
+    }
+
+    // Walks down from the root, taking the child that sets the proof number at OR nodes and the
+    // disproof number at AND nodes, and replays the moves on the way.
+    fn select_most_proving(&self, chess_board: &ChessBoard) -> (usize, ChessBoard, Vec<u64>, usize) {
+        let mut board = chess_board.clone();
+        let mut path = vec![self.nodes[0].hash];
+        let mut index = 0;
+        while self.nodes[index].expanded {
+            let node = &self.nodes[index];
+            index = *node.children.iter()
+                .find(|&&child| if node.or_node {
+                    self.nodes[child].proof == node.proof
+                } else {
+                    self.nodes[child].disproof == node.disproof
+                })
+                .expect("an unsolved node has a child setting its numbers");
+            board.update_board(self.nodes[index].chess_move.clone().expect("only the root has no move"));
+            path.push(self.nodes[index].hash);
+        }
+        let ply = path.len() - 1;
+        (index, board, path, ply)
+    }
+
+    fn expand(&mut self, index: usize, chess_board: &ChessBoard, path: &[u64], ply: usize) {
+        self.expansions += 1;
+        let or_node = self.nodes[index].or_node;
+        for chess_move in ChessBoard::get_party_moves(chess_board) {
+            let mut child = chess_board.clone();
+            let replies = child.update_board(chess_move.clone());
+            let hash = child.get_hash();
+            let (proof, disproof, distance) = if let Some(&(distance, _)) = self.proven.get(&hash) {
+                (0, INFINITE, distance)
+            } else {
+                match ChessBoard::get_party_state(&child) {
+                    // Mated side is the defender when the attacker just moved
+                    PartyState::CheckMate if or_node => {
+                        self.proven.insert(hash, (0, None));
+                        (0, INFINITE, 0)
+                    },
+                    PartyState::CheckMate | PartyState::Pat => (INFINITE, 0, 0),
+                    _ if path.contains(&hash) || ply + 1 >= self.max_plies => (INFINITE, 0, 0),
+                    // Fewer replies, closer to a proof
+                    _ if or_node => (replies.len() as u32, 1, 0),
+                    _ => (1, replies.len() as u32, 0),
+                }
+            };
+            let child_index = self.allocate(PnNode {
+                chess_move : Some(chess_move),
+                hash,
+                parent : Some(index),
+                children : vec![],
+                or_node : !or_node,
+                proof,
+                disproof,
+                distance,
+                expanded : proof == 0 || disproof == 0,
+            });
+            self.nodes[index].children.push(child_index);
+            // One proven move solves an OR node, one refutation an AND node
+            if (or_node && proof == 0) || (!or_node && disproof == 0) {
+                break;
+            }
+        }
+        self.nodes[index].expanded = true;
+    }
+
+    fn update_ancestors(&mut self, index: usize) {
+        let mut current = Some(index);
+        while let Some(index) = current {
+            let node = &self.nodes[index];
+            let children = node.children.iter().map(|&child| &self.nodes[child]);
+            let (proof, disproof) = if node.or_node {
+                (children.clone().map(|child| child.proof).min().unwrap_or(INFINITE),
+                 children.fold(0, |sum: u32, child| sum.saturating_add(child.disproof)))
+            } else {
+                (children.clone().fold(0, |sum: u32, child| sum.saturating_add(child.proof)),
+                 children.map(|child| child.disproof).min().unwrap_or(INFINITE))
+            };
+            self.nodes[index].proof = proof;
+            self.nodes[index].disproof = disproof;
+            if proof == 0 {
+                self.keep_proof(index);
+            } else if disproof == 0 {
+                let children = std::mem::take(&mut self.nodes[index].children);
+                for child in children {
+                    self.free_subtree(child);
+                }
+            }
+            current = self.nodes[index].parent;
+        }
+    }
+
+    // Records a proven node and keeps only the child of its main line: the quickest mate at an
+    // OR node, the longest defence at an AND node.
+    fn keep_proof(&mut self, index: usize) {
+        let node = &self.nodes[index];
+        let best_child = if node.or_node {
+            node.children.iter().copied()
+                .filter(|&child| self.nodes[child].proof == 0)
+                .min_by_key(|&child| self.nodes[child].distance)
+        } else {
+            node.children.iter().copied().max_by_key(|&child| self.nodes[child].distance)
+        };
+        let Some(best_child) = best_child else {
+            return;
+        };
+        let distance = self.nodes[best_child].distance + 1;
+        let chess_move = self.nodes[best_child].chess_move.clone();
+        let hash = self.nodes[index].hash;
+        // The first proof of a position stays, so the distances along the stored main line
+        // always decrease and the line cannot loop.
+        let (distance, _) = *self.proven.entry(hash).or_insert((distance, chess_move));
+        self.nodes[index].distance = distance;
+        let children = std::mem::take(&mut self.nodes[index].children);
+        for child in children {
+            if child == best_child {
+                self.nodes[index].children.push(child);
+            } else {
+                self.free_subtree(child);
+            }
+        }
+    }
+
+    fn main_line(&self, chess_board: &ChessBoard) -> Vec<ChessMove> {
+        let mut board = chess_board.clone();
+        let mut line = vec![];
+        while let Some((_, Some(chess_move))) = self.proven.get(&board.get_hash()) {
+            line.push(chess_move.clone());
+            board.update_board(chess_move.clone());
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mate_finder::{MateFinder, MateResult};
+
+    // The line found is not always the shortest mate, but every move is legal and it ends in mate
+    #[test]
+    fn proves_every_krk_position_with_a_legal_mating_line() {
+        for fen in KRK_POSITIONS.iter() {
+            let chess_board = ChessBoard::from_fen(fen).unwrap();
+            let ProofResult::Proven { moves, line } = ProofNumberSearch::new(DEFAULT_MAX_NODES, DEFAULT_MAX_MOVES).prove(&chess_board) else {
+                panic!("no proof for {}", fen);
+            };
+            assert_eq!(line.len(), 2 * moves - 1, "{}", fen);
+            let mut board = chess_board.clone();
+            let mut legal_moves = ChessBoard::get_party_moves(&board);
+            for chess_move in line.iter() {
+                assert!(legal_moves.contains(chess_move), "{:?} is not legal in {}", chess_move, fen);
+                legal_moves = board.update_board(chess_move.clone());
+            }
+            assert_eq!(ChessBoard::get_party_state(&board), PartyState::CheckMate, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn short_krk_mate_matches_the_mate_finder() {
+        let chess_board = ChessBoard::from_fen(KRK_POSITIONS[0]).unwrap();
+        let proof = ProofNumberSearch::new(DEFAULT_MAX_NODES, DEFAULT_MAX_MOVES).prove(&chess_board);
+        let MateResult::Mate { moves, .. } = MateFinder::new().find_mate(&chess_board, 3) else {
+            panic!("no mate in 3");
+        };
+        assert!(matches!(proof, ProofResult::Proven { moves: proven, .. } if proven == moves));
+    }
+
+    #[test]
+    fn small_node_limit_leaves_the_position_unknown() {
+        let chess_board = ChessBoard::from_fen(KRK_POSITIONS[3]).unwrap();
+        assert_eq!(ProofNumberSearch::new(100, DEFAULT_MAX_MOVES).prove(&chess_board), ProofResult::Unknown);
+    }
+
+    #[test]
+    fn stalemate_is_disproven() {
+        let chess_board = ChessBoard::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(ProofNumberSearch::new(DEFAULT_MAX_NODES, DEFAULT_MAX_MOVES).prove(&chess_board), ProofResult::Disproven);
+    }
+}
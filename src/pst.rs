@@ -0,0 +1,218 @@
+use crate::chess_board::{ChessBoard, Color, Piece, PieceType, Position};
+
+// Phase of the starting position: minor pieces count 1, rooks 2, queens 4
+pub const MAX_PHASE: i32 = 24;
+
+// Bonus in centipawns of each square for a White piece, drawn as seen from White: the first
+// row is the eighth rank. Black pieces read the tables upside down.
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+// En finale, seule l'avance du pion compte
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     15,  15,  15,  15,  15,  15,  15,  15,
+      5,   5,   5,   5,   5,   5,   5,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const KNIGHT: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+const BISHOP: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+const ROOK_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+const ROOK_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     10,  10,  10,  10,  10,  10,  10,  10,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const QUEEN: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+// Le roi reste à l'abri derrière ses pions tant qu'il y a des pièces...
+const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+// ... puis se centralise en finale
+const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+fn square_index(color: Color, position: Position) -> usize {
+    match color {
+        Color::White => (7 - position.row) * 8 + position.col,
+        _ => position.row * 8 + position.col,
+    }
+}
+
+// Middlegame and endgame bonus of a piece on its square
+pub fn piece_square(piece_type: PieceType, color: Color, position: Position) -> (i32, i32) {
+    let (mg, eg) = match piece_type {
+        PieceType::Pawn => (&PAWN_MG, &PAWN_EG),
+        PieceType::Knight => (&KNIGHT, &KNIGHT),
+        PieceType::Bishop => (&BISHOP, &BISHOP),
+        PieceType::Rook => (&ROOK_MG, &ROOK_EG),
+        PieceType::Queen => (&QUEEN, &QUEEN),
+        PieceType::King => (&KING_MG, &KING_EG),
+        PieceType::None => return (0, 0),
+    };
+    let index = square_index(color, position);
+    (mg[index], eg[index])
+}
+
+fn phase_weight(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Knight | PieceType::Bishop => 1,
+        PieceType::Rook => 2,
+        PieceType::Queen => 4,
+        _ => 0,
+    }
+}
+
+// From MAX_PHASE with all the pieces on the board down to 0 with only kings and pawns. Promotions
+// can push the sum above MAX_PHASE, it is capped there.
+pub fn game_phase(chess_board: &ChessBoard) -> i32 {
+    let phase: i32 = chess_board.get_friendly_pieces().iter()
+        .chain(chess_board.get_ennemy_pieces().iter())
+        .map(|piece| phase_weight(piece.piece_type))
+        .sum();
+    phase.min(MAX_PHASE)
+}
+
+// Blends a middlegame and an endgame value by the phase
+pub fn taper(mg: i32, eg: i32, phase: i32) -> i32 {
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+// Middlegame and endgame sums of the square bonuses of `pieces`
+pub fn pieces_square_sum(pieces: &[Piece]) -> (i32, i32) {
+    pieces.iter()
+        .map(|piece| piece_square(piece.piece_type, piece.color, piece.get_position()))
+        .fold((0, 0), |(mg, eg), (piece_mg, piece_eg)| (mg + piece_mg, eg + piece_eg))
+}
+
+// Middlegame and endgame square bonuses from the point of view of the side to move
+pub fn pst_terms(chess_board: &ChessBoard) -> (i32, i32) {
+    let (friend_mg, friend_eg) = pieces_square_sum(&chess_board.get_friendly_pieces());
+    let (ennemy_mg, ennemy_eg) = pieces_square_sum(&chess_board.get_ennemy_pieces());
+    (friend_mg - ennemy_mg, friend_eg - ennemy_eg)
+}
+
+// Tapered square bonuses from the point of view of the side to move
+pub fn pst_score(chess_board: &ChessBoard) -> i32 {
+    let (mg, eg) = pst_terms(chess_board);
+    taper(mg, eg, game_phase(chess_board))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::BENCH_POSITIONS;
+    use crate::chess_board::tests::mirror_fen;
+
+    const PIECE_TYPES: [PieceType; 6] = [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King];
+
+    #[test]
+    fn black_reads_the_tables_upside_down() {
+        for piece_type in PIECE_TYPES {
+            for row in 0..8 {
+                for col in 0..8 {
+                    assert_eq!(piece_square(piece_type, Color::White, Position { row, col }),
+                        piece_square(piece_type, Color::Black, Position { row: 7 - row, col }), "{:?}", piece_type);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored_positions_score_the_same_for_the_side_to_move() {
+        for fen in BENCH_POSITIONS.iter() {
+            let chess_board = ChessBoard::from_fen(fen).unwrap();
+            let mirrored = ChessBoard::from_fen(&mirror_fen(fen)).unwrap();
+            assert_eq!(pst_terms(&chess_board), pst_terms(&mirrored), "{}", fen);
+            assert_eq!(game_phase(&chess_board), game_phase(&mirrored), "{}", fen);
+            // Le même placement avec l'autre camp au trait donne le score opposé
+            let other_side = ChessBoard::from_fen(&fen.replacen(" w ", " b ", 1)).unwrap();
+            let (mg, eg) = pst_terms(&chess_board);
+            assert_eq!(pst_terms(&other_side), (-mg, -eg), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn phase_runs_from_the_opening_to_the_pawn_ending() {
+        assert_eq!(game_phase(&ChessBoard::new()), MAX_PHASE);
+        assert_eq!(game_phase(&ChessBoard::from_fen("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap()), 0);
+        assert_eq!(taper(100, -40, MAX_PHASE), 100);
+        assert_eq!(taper(100, -40, 0), -40);
+        assert_eq!(taper(100, -40, MAX_PHASE / 2), 30);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::zobrist::splitmix64;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Small seeded generator (splitmix64): the same seed replays the same games
+#[derive(Clone,Debug)]
+pub struct Rng {
+    state : u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        Rng::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let (state, value) = splitmix64(self.state);
+        self.state = state;
+        value
+    }
+
+    // Uniform in 0..n, n > 0
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
@@ -0,0 +1,131 @@
+use crate::chess_board::Color;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Moves assumed left in the game when the GUI does not send movestogo
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+// Time kept in reserve for the GUI / communication lag
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+
+#[derive(Clone,Debug,Default)]
+pub struct SearchLimits {
+    pub depth : Option<usize>,
+    pub nodes : Option<u64>,
+    pub movetime : Option<Duration>,
+    pub wtime : Option<Duration>,
+    pub btime : Option<Duration>,
+    pub winc : Option<Duration>,
+    pub binc : Option<Duration>,
+    pub movestogo : Option<u32>,
+    pub infinite : bool,
+    // Search for a mate in that many moves (not plies)
+    pub mate : Option<usize>,
+    // Search on the opponent's time: the limits only apply after `ponderhit`
+    pub ponder : bool,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: usize) -> Self {
+        SearchLimits { depth: Some(depth), ..Default::default() }
+    }
+
+    pub fn movetime(movetime: Duration) -> Self {
+        SearchLimits { movetime: Some(movetime), ..Default::default() }
+    }
+}
+
+// Shared flag polled by every search thread. Cloning it gives another handle on the same flag,
+// so a front-end can keep one and stop a search running in the background.
+#[derive(Clone,Debug,Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn new() -> Self {
+        StopSignal(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Raised while a search ponders on the opponent's time. Clearing it (ponderhit) turns the
+// search into a normal one that keeps the work already done.
+#[derive(Clone,Debug,Default)]
+pub struct PonderSignal(Arc<AtomicBool>);
+
+impl PonderSignal {
+    pub fn new(pondering: bool) -> Self {
+        PonderSignal(Arc::new(AtomicBool::new(pondering)))
+    }
+
+    pub fn ponderhit(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Time and node budget of one search, derived from the limits and the side to move.
+#[derive(Clone,Copy,Debug)]
+pub struct SearchClock {
+    start : Instant,
+    // Past this point no new iteration is started
+    soft_limit : Option<Duration>,
+    // Past this point the search is stopped wherever it is
+    hard_limit : Option<Duration>,
+    node_limit : Option<u64>,
+}
+
+impl SearchClock {
+    pub fn new(limits: &SearchLimits, turn: Color) -> Self {
+        let start = Instant::now();
+        let (time_left, increment) = match turn {
+            Color::White => (limits.wtime, limits.winc),
+            _ => (limits.btime, limits.binc),
+        };
+        let (mut soft_limit, mut hard_limit) = (None, None);
+        if let Some(time_left) = time_left {
+            let increment = increment.unwrap_or(Duration::ZERO);
+            let moves_to_go = limits.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+            let available = time_left.saturating_sub(MOVE_OVERHEAD);
+            let optimum = (available / moves_to_go + increment * 3 / 4).min(available);
+            soft_limit = Some(optimum);
+            hard_limit = Some((optimum * 4).min(available * 4 / 5).max(optimum));
+        }
+        if let Some(movetime) = limits.movetime {
+            let movetime = movetime.saturating_sub(MOVE_OVERHEAD).max(Duration::from_millis(1));
+            soft_limit = Some(movetime);
+            hard_limit = Some(movetime);
+        }
+        if limits.infinite {
+            soft_limit = None;
+            hard_limit = None;
+        }
+        SearchClock { start, soft_limit, hard_limit, node_limit: limits.nodes }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        self.soft_limit.is_some_and(|limit| self.elapsed() >= limit)
+    }
+
+    pub fn has_node_limit(&self) -> bool {
+        self.node_limit.is_some()
+    }
+
+    pub fn hard_limit_reached(&self, nodes: u64) -> bool {
+        self.node_limit.is_some_and(|limit| nodes >= limit)
+            || self.hard_limit.is_some_and(|limit| self.elapsed() >= limit)
+    }
+}
@@ -0,0 +1,320 @@
+use crate::chess_board::ChessBoard;
+use crate::evaluator::{Evaluator, PositionalEvaluator};
+use crate::minimax::{nodes_per_second, PvLine, Score, SearchParams, SearchResult, SearchStats, SearchThread, ThreadReport};
+use crate::observer::SearchObserver;
+use crate::search_limits::{PonderSignal, SearchClock, SearchLimits, StopSignal};
+use crate::transposition::TranspositionTable;
+use std::sync::atomic::AtomicU64;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+#[derive(Clone,Debug)]
+pub struct SearchConfig {
+    pub threads : usize,
+    pub hash_size_mb : usize,
+    pub null_move : bool,
+    pub late_move_reductions : bool,
+    pub futility_pruning : bool,
+    pub razoring : bool,
+    pub delta_pruning : bool,
+    pub check_extensions : bool,
+    pub single_reply_extensions : bool,
+    pub recapture_extensions : bool,
+    pub aspiration_windows : bool,
+    // Number of best root moves reported with their own line
+    pub multi_pv : usize,
+    // Centipawns a draw is worth less than zero to the engine
+    pub contempt : i32,
+    // Same move for the same position and depth or node limit: one thread and an empty
+    // transposition table at every search. Time limits still depend on the machine.
+    pub deterministic : bool,
+    pub evaluator : Arc<dyn Evaluator>,
+    pub params : SearchParams,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            threads : thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            hash_size_mb : 16,
+            null_move : true,
+            late_move_reductions : true,
+            futility_pruning : true,
+            razoring : true,
+            delta_pruning : true,
+            check_extensions : true,
+            single_reply_extensions : true,
+            recapture_extensions : false,
+            aspiration_windows : true,
+            multi_pv : 1,
+            contempt : 0,
+            deterministic : false,
+            evaluator : Arc::new(PositionalEvaluator::default()),
+            params : SearchParams::default(),
+        }
+    }
+}
+
+impl SearchConfig {
+    pub fn thread_count(&self) -> usize {
+        if self.deterministic { 1 } else { self.threads.max(1) }
+    }
+}
+
+struct Job {
+    chess_board : ChessBoard,
+    config : SearchConfig,
+    limits : SearchLimits,
+    clock : SearchClock,
+    stop : StopSignal,
+    ponder : PonderSignal,
+    // Only the main thread reports to the observer
+    observer : Option<Arc<dyn SearchObserver>>,
+    shared_nodes : Arc<AtomicU64>,
+    report_tx : Sender<ThreadReport>,
+}
+
+struct Worker {
+    job_tx : Option<Sender<Job>>,
+    thread : Option<JoinHandle<()>>,
+}
+
+// Worker threads are spawned once and reused for every move of the game; they share one
+// transposition table so that a search also benefits from the previous ones.
+pub struct SearchPool {
+    config : SearchConfig,
+    tt : Arc<TranspositionTable>,
+    workers : Vec<Worker>,
+    observer : Option<Arc<dyn SearchObserver>>,
+}
+
+// A search running in the background. The best move found so far is returned by `wait`
+// once the search reaches its limits or is stopped. Dropping the handle stops the search, an
+// infinite or ponder search would otherwise hold the workers forever.
+pub struct SearchHandle {
+    stop : StopSignal,
+    ponder : PonderSignal,
+    multi_pv : usize,
+    clock : SearchClock,
+    report_rx : Receiver<ThreadReport>,
+}
+
+impl SearchPool {
+    pub fn new(config: SearchConfig) -> Self {
+        let tt = Arc::new(TranspositionTable::new(config.hash_size_mb));
+        let workers = (0..config.thread_count())
+            .map(|id| Worker::spawn(id, tt.clone()))
+            .collect();
+        SearchPool { config, tt, workers, observer: None }
+    }
+
+    // Progress of every following search is sent to `observer`
+    pub fn set_observer(&mut self, observer: Arc<dyn SearchObserver>) {
+        self.observer = Some(observer);
+    }
+
+    // Forgets every stored position, as before a new game
+    pub fn clear_hash(&self) {
+        self.tt.clear();
+    }
+
+    pub fn start_search(&mut self, chess_board: &ChessBoard, limits: SearchLimits) -> SearchHandle {
+        if self.config.deterministic {
+            self.tt.clear();
+        }
+        self.tt.new_search();
+        let stop = StopSignal::new();
+        let ponder = PonderSignal::new(limits.ponder);
+        let clock = SearchClock::new(&limits, ChessBoard::get_turn(chess_board));
+        let shared_nodes = Arc::new(AtomicU64::new(0));
+        let (report_tx, report_rx) = mpsc::channel();
+        for (id, worker) in self.workers.iter().enumerate() {
+            let job = Job {
+                chess_board : chess_board.clone(),
+                config : self.config.clone(),
+                limits : limits.clone(),
+                clock,
+                stop : stop.clone(),
+                ponder : ponder.clone(),
+                observer : if id == 0 { self.observer.clone() } else { None },
+                shared_nodes : shared_nodes.clone(),
+                report_tx : report_tx.clone(),
+            };
+            worker.job_tx.as_ref().expect("worker is running").send(job).expect("worker thread died");
+        }
+        SearchHandle { stop, ponder, multi_pv: self.config.multi_pv, clock, report_rx }
+    }
+
+    pub fn start_minimax(&mut self, chess_board: &ChessBoard, limits: SearchLimits) -> SearchResult {
+        self.start_search(chess_board, limits).wait()
+    }
+}
+
+impl SearchHandle {
+    pub fn stop_signal(&self) -> StopSignal {
+        self.stop.clone()
+    }
+
+    pub fn stop(&self) {
+        self.stop.stop();
+    }
+
+    // The opponent played the expected move: the search goes on under its normal limits
+    pub fn ponderhit(&self) {
+        self.ponder.ponderhit();
+    }
+
+    pub fn wait(self) -> SearchResult {
+        let reports: Vec<ThreadReport> = self.report_rx.iter().collect();
+        let nodes = reports.iter().map(|report| report.nodes).sum();
+        let mut stats = SearchStats::default();
+        for report in reports.iter() {
+            stats.add(&report.stats);
+        }
+        // The deepest completed iteration wins, the main thread on ties. Only the main thread
+        // searches the MultiPV lines, so it is the one reported in that mode.
+        let multi_pv = self.multi_pv;
+        let best_report = reports.into_iter()
+            .filter(|report| report.best_move.is_some() && (multi_pv <= 1 || report.id == 0))
+            .max_by(|a, b| a.depth.cmp(&b.depth).then(b.id.cmp(&a.id)));
+        let elapsed = self.clock.elapsed();
+        let nps = nodes_per_second(nodes, elapsed);
+        match best_report {
+            Some(report) => SearchResult {
+                best_move : report.best_move,
+                score : Score::from_value(report.score),
+                principal_variation : report.principal_variation,
+                lines : report.lines.into_iter()
+                    .map(|(score, principal_variation)| PvLine { score: Score::from_value(score), principal_variation })
+                    .collect(),
+                depth : report.depth,
+                seldepth : report.seldepth,
+                nodes,
+                elapsed,
+                nps,
+                stats,
+                root_visits : vec![],
+            },
+            None => SearchResult {
+                best_move : None,
+                score : Score::Cp(0),
+                principal_variation : vec![],
+                lines : vec![],
+                depth : 0,
+                seldepth : 0,
+                nodes,
+                elapsed,
+                nps,
+                stats,
+                root_visits : vec![],
+            },
+        }
+    }
+}
+
+impl Drop for SearchHandle {
+    fn drop(&mut self) {
+        self.stop.stop();
+    }
+}
+
+impl Drop for SearchPool {
+    fn drop(&mut self) {
+        for worker in self.workers.iter_mut() {
+            worker.job_tx.take();
+        }
+        for worker in self.workers.iter_mut() {
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Worker {
+    fn spawn(id: usize, tt: Arc<TranspositionTable>) -> Self {
+        let (job_tx, job_rx): (Sender<Job>, Receiver<Job>) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            for job in job_rx {
+                let mut search_thread = SearchThread::new(id, tt.clone(), job.config, job.stop.clone(), job.ponder, job.clock, job.shared_nodes);
+                if let Some(observer) = job.observer {
+                    search_thread.set_observer(observer);
+                }
+                let report = search_thread.iterative_deepening(&job.chess_board, &job.limits);
+                if id == 0 {
+                    // Main thread is done: helpers are only useful while it searches
+                    job.stop.stop();
+                }
+                let _ = job.report_tx.send(report);
+            }
+        });
+        Worker { job_tx: Some(job_tx), thread: Some(thread) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::BENCH_POSITIONS;
+
+    fn deterministic_search(search_pool: &mut SearchPool, fen: &str, limits: SearchLimits) -> SearchResult {
+        search_pool.start_minimax(&ChessBoard::from_fen(fen).unwrap(), limits)
+    }
+
+    #[test]
+    fn stopped_search_returns_a_legal_move() {
+        let chess_board = ChessBoard::from_fen(BENCH_POSITIONS[2]).unwrap();
+        let mut search_pool = SearchPool::new(SearchConfig { threads: 2, ..SearchConfig::default() });
+        let search_handle = search_pool.start_search(&chess_board, SearchLimits { infinite: true, ..Default::default() });
+        thread::sleep(std::time::Duration::from_millis(200));
+        search_handle.stop();
+        let search_result = search_handle.wait();
+        let best_move = search_result.best_move.expect("a stopped search still has a move");
+        assert!(ChessBoard::get_party_moves(&chess_board).contains(&best_move));
+        assert!(search_result.depth >= 1);
+    }
+
+    #[test]
+    fn dropped_handle_frees_the_workers() {
+        let chess_board = ChessBoard::new();
+        let mut search_pool = SearchPool::new(SearchConfig { threads: 2, ..SearchConfig::default() });
+        drop(search_pool.start_search(&chess_board, SearchLimits { ponder: true, ..Default::default() }));
+        let search_result = search_pool.start_minimax(&chess_board, SearchLimits::depth(2));
+        assert_eq!(search_result.depth, 2);
+    }
+
+    #[test]
+    fn small_node_limits_are_kept() {
+        // Far below the interval between two clock checks, as the lowest skill levels use. The
+        // first iteration always completes, it stays well under 200 nodes here.
+        let chess_board = ChessBoard::new();
+        let mut search_pool = SearchPool::new(SearchConfig { threads: 1, ..SearchConfig::default() });
+        for limit in [200, 400, 800] {
+            let nodes = search_pool.start_minimax(&chess_board, SearchLimits { nodes: Some(limit), ..Default::default() }).nodes;
+            assert!((limit..=limit + 10).contains(&nodes), "{} nodes for a limit of {}", nodes, limit);
+        }
+    }
+
+    #[test]
+    fn deterministic_searches_repeat_exactly() {
+        let config = SearchConfig { threads: 4, deterministic: true, ..SearchConfig::default() };
+        let limits = [SearchLimits::depth(3), SearchLimits { nodes: Some(3_000), ..Default::default() }];
+        // Opening, middlegame and endgame
+        for fen in [BENCH_POSITIONS[0], BENCH_POSITIONS[3], BENCH_POSITIONS[4]] {
+            for limits in limits.iter() {
+                // A reused pool must not remember the previous search, a new pool must agree with it
+                let mut search_pool = SearchPool::new(config.clone());
+                let first = deterministic_search(&mut search_pool, fen, limits.clone());
+                let second = deterministic_search(&mut search_pool, fen, limits.clone());
+                let third = deterministic_search(&mut SearchPool::new(config.clone()), fen, limits.clone());
+                for other in [&second, &third] {
+                    assert_eq!(first.best_move, other.best_move, "{}", fen);
+                    assert_eq!(first.score, other.score, "{}", fen);
+                    assert_eq!(first.nodes, other.nodes, "{}", fen);
+                    assert_eq!(first.principal_variation, other.principal_variation, "{}", fen);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::chess_board::{ChessBoard, ChessMove, PartyState};
+use crate::mcts::{Mcts, MctsConfig, Playout};
+use crate::minimax::{nodes_per_second, piece_value, Score, SearchResult, SearchStats};
+use crate::observer::SearchObserver;
+use crate::rng::Rng;
+use crate::search_limits::SearchLimits;
+use crate::search_pool::{SearchConfig, SearchHandle, SearchPool};
+use std::sync::Arc;
+use std::time::Instant;
+
+pub const ENGINES: [&str; 5] = ["minimax", "mcts", "mcts-eval", "random", "greedy"];
+
+// Anything able to pick a move: the front-ends only talk to this trait, so a new algorithm
+// is added by implementing it and registering its name in `create_searcher`.
+pub trait Searcher {
+    fn search(&mut self, chess_board: &ChessBoard, limits: SearchLimits) -> SearchResult;
+
+    // Starts searching in the background the position expected after the opponent's reply.
+    // Searchers that cannot run in the background do not ponder.
+    fn start_ponder(&mut self, _chess_board: &ChessBoard, _limits: SearchLimits) -> Option<SearchHandle> {
+        None
+    }
+
+    // Live progress of the following searches goes to `observer`. Searchers without
+    // iterations to report ignore it.
+    fn set_observer(&mut self, _observer: Arc<dyn SearchObserver>) {}
+}
+
+pub fn create_searcher(name: &str, config: SearchConfig, rng: Rng) -> Result<Box<dyn Searcher>, String> {
+    match name {
+        "minimax" => Ok(Box::new(SearchPool::new(config))),
+        "mcts" => Ok(Box::new(Mcts::new(MctsConfig { threads: config.thread_count(), evaluator: config.evaluator, ..MctsConfig::default() }, rng))),
+        "mcts-eval" => Ok(Box::new(Mcts::new(MctsConfig { threads: config.thread_count(), playout: Playout::EvalGuided, evaluator: config.evaluator, ..MctsConfig::default() }, rng))),
+        "random" => Ok(Box::new(RandomMover::new(rng))),
+        "greedy" => Ok(Box::new(GreedyCapturer::new(rng))),
+        _ => Err(format!("unknown engine {} (expected one of {})", name, ENGINES.join(", "))),
+    }
+}
+
+impl Searcher for SearchPool {
+    fn search(&mut self, chess_board: &ChessBoard, limits: SearchLimits) -> SearchResult {
+        self.start_minimax(chess_board, limits)
+    }
+
+    fn start_ponder(&mut self, chess_board: &ChessBoard, limits: SearchLimits) -> Option<SearchHandle> {
+        Some(self.start_search(chess_board, SearchLimits { ponder: true, ..limits }))
+    }
+
+    fn set_observer(&mut self, observer: Arc<dyn SearchObserver>) {
+        SearchPool::set_observer(self, observer);
+    }
+}
+
+// Plays any legal move, as a baseline opponent
+pub struct RandomMover {
+    rng : Rng,
+}
+
+impl RandomMover {
+    pub fn new(rng: Rng) -> Self {
+        RandomMover { rng }
+    }
+}
+
+impl Searcher for RandomMover {
+    fn search(&mut self, chess_board: &ChessBoard, _limits: SearchLimits) -> SearchResult {
+        let start = Instant::now();
+        let moves = ChessBoard::get_party_moves(chess_board);
+        let best_move = (!moves.is_empty()).then(|| moves[self.rng.below(moves.len())].clone());
+        one_ply_result(best_move, Score::Cp(0), moves.len() as u64, start)
+    }
+}
+
+// Mates in one when it can, otherwise takes the most valuable piece en prise without looking
+// at the recapture; quiet positions fall back to a random move.
+pub struct GreedyCapturer {
+    rng : Rng,
+}
+
+impl GreedyCapturer {
+    pub fn new(rng: Rng) -> Self {
+        GreedyCapturer { rng }
+    }
+}
+
+impl Searcher for GreedyCapturer {
+    fn search(&mut self, chess_board: &ChessBoard, _limits: SearchLimits) -> SearchResult {
+        let start = Instant::now();
+        let moves = ChessBoard::get_party_moves(chess_board);
+        let scored: Vec<(i32, ChessMove)> = moves.into_iter()
+            .map(|chess_move| (greedy_score(chess_board, &chess_move), chess_move))
+            .collect();
+        let Some(best_score) = scored.iter().map(|(score, _)| *score).max() else {
+            return one_ply_result(None, Score::Cp(0), 0, start);
+        };
+        let best_moves: Vec<&ChessMove> = scored.iter()
+            .filter(|(score, _)| *score == best_score)
+            .map(|(_, chess_move)| chess_move)
+            .collect();
+        let best_move = best_moves[self.rng.below(best_moves.len())].clone();
+        let score = if best_score == i32::MAX { Score::Mate(1) } else { Score::Cp(best_score) };
+        one_ply_result(Some(best_move), score, scored.len() as u64, start)
+    }
+}
+
+fn greedy_score(chess_board: &ChessBoard, chess_move: &ChessMove) -> i32 {
+    if chess_board.gives_check(chess_move) {
+        let mut chess_board_clone = chess_board.clone();
+        chess_board_clone.update_board(chess_move.clone());
+        if ChessBoard::get_party_state(&chess_board_clone) == PartyState::CheckMate {
+            return i32::MAX;
+        }
+    }
+    match chess_move {
+        ChessMove::OrdinaryMove { from: _, to } => chess_board.get_piece_from_pos(to)
+            .map_or(0, |piece| piece_value(piece.piece_type)),
+        ChessMove::Castle { .. } => 0,
+    }
+}
+
+fn one_ply_result(best_move: Option<ChessMove>, score: Score, nodes: u64, start: Instant) -> SearchResult {
+    let elapsed = start.elapsed();
+    SearchResult {
+        principal_variation : best_move.iter().cloned().collect(),
+        best_move,
+        score,
+        lines : vec![],
+        depth : 1,
+        seldepth : 1,
+        nodes,
+        elapsed,
+        nps : nodes_per_second(nodes, elapsed),
+        stats : SearchStats::default(),
+        root_visits : vec![],
+    }
+}
@@ -0,0 +1,133 @@
+use crate::chess_board::ChessBoard;
+use crate::minimax::{Score, SearchResult};
+use crate::observer::SearchObserver;
+use crate::rng::Rng;
+use crate::search_limits::SearchLimits;
+use crate::search_pool::{SearchConfig, SearchPool};
+use crate::searcher::Searcher;
+use std::sync::Arc;
+
+pub const MAX_SKILL_LEVEL: u8 = 20;
+// Lines searched below full strength, among which a weaker move may be chosen
+const SKILL_MULTI_PV: usize = 4;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct SkillLevel {
+    pub level : u8,
+    // None at full strength: the caller's limits apply unchanged
+    pub depth : Option<usize>,
+    pub nodes : Option<u64>,
+    // Largest random offset added to the evaluation, in centipawns
+    pub eval_noise : i32,
+    // Largest loss against the best line accepted when picking a weaker move
+    pub max_loss : i32,
+    // Rough strength guess for the players choosing a level, not a measured rating
+    pub elo : u32,
+}
+
+impl SkillLevel {
+    // Level 0 plays one ply deep with heavy noise, level 20 is the unrestricted engine
+    pub fn new(level: u8) -> Self {
+        let level = level.min(MAX_SKILL_LEVEL);
+        let weakness = (MAX_SKILL_LEVEL - level) as i32;
+        if weakness == 0 {
+            return SkillLevel { level, depth: None, nodes: None, eval_noise: 0, max_loss: 0, elo: 1500 };
+        }
+        SkillLevel {
+            level,
+            depth : Some(1 + level as usize / 4),
+            nodes : Some(200 << (level / 2)),
+            eval_noise : weakness * 15,
+            max_loss : weakness * 20,
+            elo : 600 + level as u32 * 45,
+        }
+    }
+
+    pub fn is_full_strength(&self) -> bool {
+        self.level == MAX_SKILL_LEVEL
+    }
+}
+
+// Minimax restricted to a skill level: shallower, noisier, and free to play one of the MultiPV
+// lines that is worse than the best by less than `max_loss`.
+pub struct SkilledSearcher {
+    search_pool : SearchPool,
+    skill : SkillLevel,
+    rng : Rng,
+}
+
+impl SkilledSearcher {
+    pub fn new(config: SearchConfig, level: u8, mut rng: Rng) -> Self {
+        let skill = SkillLevel::new(level);
+        let mut config = config;
+        if !skill.is_full_strength() {
+            // Helper threads would add their own nodes on top of the level's node cap
+            config.threads = 1;
+            config.multi_pv = config.multi_pv.max(SKILL_MULTI_PV);
+            config.params.eval_noise = skill.eval_noise;
+            config.params.noise_seed = rng.next_u64();
+        }
+        SkilledSearcher { search_pool: SearchPool::new(config), skill, rng }
+    }
+
+    pub fn skill(&self) -> SkillLevel {
+        self.skill
+    }
+
+    // Weaker lines are drawn with a weight falling linearly with their loss. A forced mate is
+    // never given away.
+    fn pick_line(&mut self, search_result: &SearchResult) -> Option<usize> {
+        let best_score = search_result.lines.first()?.score;
+        if let Score::Mate(moves) = best_score {
+            if moves > 0 {
+                return None;
+            }
+        }
+        let best_value = best_score.to_value();
+        let weights: Vec<i32> = search_result.lines.iter()
+            .map(|line| (self.skill.max_loss - (best_value - line.score.to_value())).max(0))
+            .collect();
+        let total: i32 = weights.iter().sum();
+        if total <= 0 {
+            return None;
+        }
+        let mut draw = self.rng.below(total as usize) as i32;
+        for (index, weight) in weights.iter().enumerate() {
+            if draw < *weight {
+                return Some(index);
+            }
+            draw -= weight;
+        }
+        None
+    }
+}
+
+impl Searcher for SkilledSearcher {
+    fn search(&mut self, chess_board: &ChessBoard, limits: SearchLimits) -> SearchResult {
+        let limits = SearchLimits {
+            depth : min_limit(limits.depth, self.skill.depth),
+            nodes : min_limit(limits.nodes, self.skill.nodes),
+            ..limits
+        };
+        let mut search_result = self.search_pool.start_minimax(chess_board, limits);
+        if let Some(index) = self.pick_line(&search_result) {
+            let line = search_result.lines[index].clone();
+            search_result.best_move = line.principal_variation.first().cloned();
+            search_result.score = line.score;
+            search_result.principal_variation = line.principal_variation;
+        }
+        search_result
+    }
+
+    // The lines reported are the searched ones, before a weaker move is picked
+    fn set_observer(&mut self, observer: Arc<dyn SearchObserver>) {
+        self.search_pool.set_observer(observer);
+    }
+}
+
+fn min_limit<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
@@ -0,0 +1,144 @@
+use crate::chess_board::{ChessMove, Position};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Debug)]
+pub struct TtEntry {
+    pub best_move: Option<ChessMove>,
+    pub score: i32,
+    pub depth: usize,
+    pub bound: Bound,
+}
+
+// Each slot stores the key xor-ed with the data next to the data itself ("lockless hashing"):
+// a torn write from two threads makes the xor check fail and the slot is treated as empty.
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    generation: AtomicU8,
+}
+
+const SLOT_SIZE: usize = std::mem::size_of::<Slot>();
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let len = ((size_mb.max(1) * 1024 * 1024) / SLOT_SIZE).max(1);
+        let slots = (0..len)
+            .map(|_| Slot { check: AtomicU64::new(0), data: AtomicU64::new(0) })
+            .collect();
+        TranspositionTable { slots, generation: AtomicU8::new(0) }
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TtEntry> {
+        let slot = &self.slots[self.index(hash)];
+        let data = slot.data.load(Ordering::Relaxed);
+        let check = slot.check.load(Ordering::Relaxed);
+        if data == 0 || check ^ data != hash {
+            return None;
+        }
+        Some(unpack(data))
+    }
+
+    pub fn store(&self, hash: u64, best_move: Option<&ChessMove>, score: i32, depth: usize, bound: Bound) {
+        let slot = &self.slots[self.index(hash)];
+        let generation = self.generation.load(Ordering::Relaxed) & 0x3F;
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let old_check = slot.check.load(Ordering::Relaxed);
+        if old_data != 0 && old_check ^ old_data == hash {
+            // Même position : on garde l'entrée la plus profonde de la recherche courante
+            let old = unpack(old_data);
+            if unpack_generation(old_data) == generation && old.depth > depth && bound != Bound::Exact {
+                return;
+            }
+        }
+        else if old_data != 0 && unpack_generation(old_data) == generation && unpack(old_data).depth > depth + 2 {
+            return;
+        }
+        let data = pack(best_move, score, depth, bound, generation);
+        slot.check.store(hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        ((hash as u128 * self.slots.len() as u128) >> 64) as usize
+    }
+}
+
+// Layout : move (16 bits) | score (32 bits) | depth (8 bits) | bound (2 bits) | generation (6 bits)
+fn pack(best_move: Option<&ChessMove>, score: i32, depth: usize, bound: Bound, generation: u8) -> u64 {
+    let bound_bits: u64 = match bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    encode_move(best_move) as u64
+        | (score as u32 as u64) << 16
+        | (depth.min(255) as u64) << 48
+        | bound_bits << 56
+        | ((generation & 0x3F) as u64) << 58
+}
+
+fn unpack(data: u64) -> TtEntry {
+    let bound = match (data >> 56) & 0b11 {
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => Bound::Exact,
+    };
+    TtEntry {
+        best_move: decode_move(data as u16),
+        score: (data >> 16) as u32 as i32,
+        depth: ((data >> 48) & 0xFF) as usize,
+        bound,
+    }
+}
+
+fn unpack_generation(data: u64) -> u8 {
+    ((data >> 58) & 0x3F) as u8
+}
+
+const MOVE_PRESENT: u16 = 1 << 15;
+const MOVE_CASTLE: u16 = 1 << 12;
+
+fn encode_move(chess_move: Option<&ChessMove>) -> u16 {
+    let square = |p: &Position| (p.row * 8 + p.col) as u16;
+    match chess_move {
+        Some(ChessMove::OrdinaryMove { from, to }) => MOVE_PRESENT | square(from) | square(to) << 6,
+        Some(ChessMove::Castle { king, rook }) => MOVE_PRESENT | MOVE_CASTLE | square(king) | square(rook) << 6,
+        None => 0,
+    }
+}
+
+fn decode_move(bits: u16) -> Option<ChessMove> {
+    if bits & MOVE_PRESENT == 0 {
+        return None;
+    }
+    let position = |square: u16| Position { row: (square / 8) as usize, col: (square % 8) as usize };
+    let first = position(bits & 0x3F);
+    let second = position((bits >> 6) & 0x3F);
+    if bits & MOVE_CASTLE != 0 {
+        Some(ChessMove::Castle { king: first, rook: second })
+    } else {
+        Some(ChessMove::OrdinaryMove { from: first, to: second })
+    }
+}
@@ -0,0 +1,83 @@
+use crate::chess_board::{Color, PieceType, Position};
+
+// Clés générées à la compilation avec splitmix64, pour que le hash d'une position
+// soit identique d'une exécution à l'autre.
+pub(crate) const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_piece_keys() -> [[u64; 64]; 12] {
+    let mut keys = [[0u64; 64]; 12];
+    let mut state = 0x2545_F491_4F6C_DD1D;
+    let mut piece = 0;
+    while piece < 12 {
+        let mut square = 0;
+        while square < 64 {
+            let (next_state, key) = splitmix64(state);
+            state = next_state;
+            keys[piece][square] = key;
+            square += 1;
+        }
+        piece += 1;
+    }
+    keys
+}
+
+// Blancs petit et grand roque, puis noirs petit et grand roque
+const fn generate_castling_keys() -> [u64; 4] {
+    let mut keys = [0u64; 4];
+    let mut state = 0x1405_7B7E_F767_814F;
+    let mut index = 0;
+    while index < 4 {
+        let (next_state, key) = splitmix64(state);
+        state = next_state;
+        keys[index] = key;
+        index += 1;
+    }
+    keys
+}
+
+const PIECE_KEYS: [[u64; 64]; 12] = generate_piece_keys();
+const CASTLING_KEYS: [u64; 4] = generate_castling_keys();
+const BLACK_TO_MOVE_KEY: u64 = splitmix64(0x5851_F42D_4C95_7F2D).1;
+
+fn piece_index(piece_type: PieceType, color: Color) -> Option<usize> {
+    let type_index = match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+        PieceType::None => return None,
+    };
+    match color {
+        Color::White => Some(type_index),
+        Color::Black => Some(type_index + 6),
+        Color::None => None,
+    }
+}
+
+pub fn piece_key(piece_type: PieceType, color: Color, position: Position) -> u64 {
+    match piece_index(piece_type, color) {
+        Some(index) => PIECE_KEYS[index][position.row * 8 + position.col],
+        None => 0,
+    }
+}
+
+pub fn turn_key(turn: Color) -> u64 {
+    if turn == Color::Black { BLACK_TO_MOVE_KEY } else { 0 }
+}
+
+pub fn castling_key(color: Color, king_side: bool) -> u64 {
+    let index = if king_side { 0 } else { 1 };
+    match color {
+        Color::White => CASTLING_KEYS[index],
+        Color::Black => CASTLING_KEYS[index + 2],
+        Color::None => 0,
+    }
+}